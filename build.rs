@@ -0,0 +1,13 @@
+// -- build.rs --
+
+use std::process::Command;
+
+// 有build.rs时cargo才设置OUT_DIR，集成测试打开idl、python等feature时导出的文件写在这里；
+// 找到python3时设置servant_python3，否则忽略Python客户端的一致性测试
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(servant_python3)");
+    if Command::new("python3").arg("--version").output().is_ok() {
+        println!("cargo:rustc-cfg=servant_python3");
+    }
+}
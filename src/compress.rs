@@ -0,0 +1,169 @@
+// -- compress.rs --
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::Ident;
+
+// --
+
+pub(crate) const ZSTD_STR: &str = "zstd";
+pub(crate) const LZ4_STR: &str = "lz4";
+
+// 压缩的请求和响应，在数据之前有一个flag，表示压缩的算法；没有压缩的是原来的bincode数据，没有flag。
+// invoke的frame和ServantResult的第一个字节是0或者1，不会与这些flag相同
+const FLAG_LZ4: u8 = 0xfd;
+const FLAG_ZSTD: u8 = 0xfe;
+
+// --
+
+// 方法使用的压缩：Some((flag, threshold))，序列化之后超过threshold字节的数据才压缩
+pub(crate) fn render_compression(compress: Option<&str>, threshold: usize) -> TokenStream2 {
+    match compress {
+        Some(ZSTD_STR) => quote! { Some((#FLAG_ZSTD, #threshold)) },
+        Some(LZ4_STR) => quote! { Some((#FLAG_LZ4, #threshold)) },
+        _ => quote! { None },
+    }
+}
+
+// 在request上生成压缩和解压的方法，只使用接口中用到的算法；没有方法压缩时什么也不生成
+pub(crate) fn render_codec(
+    request_ident: &Ident,
+    fn_ident_camel_vec: &[Ident],
+    compression_vec: &[TokenStream2],
+    compress_vec: &[Option<&str>],
+) -> TokenStream2 {
+    if compress_vec.iter().all(Option::is_none) {
+        return TokenStream2::new();
+    }
+    let request_ident_vec: Vec<_> = fn_ident_camel_vec.iter().map(|_| request_ident).collect();
+    let (zstd_compress, zstd_decompress) = if compress_vec.contains(&Some(ZSTD_STR)) {
+        (
+            quote! {
+                #FLAG_ZSTD => zstd::stream::encode_all(&bytes[..], 0).ok(),
+            },
+            quote! {
+                Some(&#FLAG_ZSTD) => {
+                    let decoder = zstd::stream::Decoder::new(&bytes[1..]).map_err(|e| e.to_string())?;
+                    let mut plain = Vec::new();
+                    std::io::Read::read_to_end(
+                        &mut std::io::Read::take(decoder, (limit as u64).saturating_add(1)),
+                        &mut plain,
+                    )
+                    .map_err(|e| e.to_string())?;
+                    plain
+                }
+            },
+        )
+    } else {
+        (TokenStream2::new(), TokenStream2::new())
+    };
+    let (lz4_compress, lz4_decompress) = if compress_vec.contains(&Some(LZ4_STR)) {
+        (
+            quote! {
+                #FLAG_LZ4 => Some(lz4_flex::compress_prepend_size(&bytes)),
+            },
+            quote! {
+                Some(&#FLAG_LZ4) => {
+                    let (size, compressed) =
+                        lz4_flex::block::uncompressed_size(&bytes[1..]).map_err(|e| e.to_string())?;
+                    if size > limit {
+                        return Err(format!("frame size {} exceeds max_frame_size {}.", size, limit));
+                    }
+                    lz4_flex::decompress(compressed, size).map_err(|e| e.to_string())?
+                }
+            },
+        )
+    } else {
+        (TokenStream2::new(), TokenStream2::new())
+    };
+    quote! {
+        impl #request_ident {
+            #[allow(dead_code)]
+            fn compression(&self) -> Option<(u8, usize)> {
+                match self {
+                    #(#request_ident_vec::#fn_ident_camel_vec{ .. } => #compression_vec,)*
+                }
+            }
+            // 只有压缩之后变小的数据才加上flag，否则是原来的数据
+            #[allow(dead_code)]
+            fn compress(compression: Option<(u8, usize)>, bytes: Vec<u8>) -> Vec<u8> {
+                let (flag, threshold) = match compression {
+                    Some(c) if bytes.len() > c.1 => c,
+                    _ => return bytes,
+                };
+                let compressed = match flag {
+                    #zstd_compress
+                    #lz4_compress
+                    _ => None,
+                };
+                match compressed.filter(|c| c.len() < bytes.len()) {
+                    Some(compressed) => {
+                        let mut v = Vec::with_capacity(compressed.len() + 1);
+                        v.push(flag);
+                        v.extend(compressed);
+                        v
+                    }
+                    None => bytes,
+                }
+            }
+            #[allow(dead_code)]
+            fn is_compressed(bytes: &[u8]) -> bool {
+                matches!(bytes.first(), Some(&#FLAG_LZ4) | Some(&#FLAG_ZSTD))
+            }
+            // 返回值中的bool表示数据是否压缩过；解压之后的数据不能超过limit字节
+            #[allow(dead_code)]
+            fn decompress(bytes: Vec<u8>, limit: Option<usize>) -> Result<(bool, Vec<u8>), String> {
+                let limit = limit.unwrap_or(usize::MAX);
+                let plain = match bytes.first() {
+                    #zstd_decompress
+                    #lz4_decompress
+                    _ => return Ok((false, bytes)),
+                };
+                if plain.len() > limit {
+                    return Err(format!("frame size {} exceeds max_frame_size {}.", plain.len(), limit));
+                }
+                Ok((true, plain))
+            }
+        }
+    }
+}
+
+// 服务端解压请求，解压失败时以ServantError执行on_error。请求压缩过，或者Context中有accept_compress时，
+// accept表示proxy可以接收压缩的响应
+pub(crate) fn render_decompress(
+    request_ident: &Ident,
+    max_frame_size: Option<usize>,
+    on_error: &TokenStream2,
+) -> TokenStream2 {
+    let limit = if let Some(max) = max_frame_size {
+        quote! { Some(#max) }
+    } else {
+        quote! { None }
+    };
+    quote! {
+        let (compressed, req) = match #request_ident::decompress(req, #limit) {
+            Ok(v) => v,
+            Err(e) => {
+                let e = servant::ServantError::from(format!("invalid frame: {}", e));
+                #on_error
+            }
+        };
+        let accept = compressed || __servant_ctx.as_ref().map_or(false, |c| c.accept_compress());
+    }
+}
+
+// proxy解压响应，response是ServantResult<Vec<u8>>；收到压缩的响应之后，在accepted中记录servant可以解压请求
+pub(crate) fn render_decompress_response(
+    request_ident: &Ident,
+    accepted: &TokenStream2,
+) -> TokenStream2 {
+    quote! {
+        response.and_then(|v| {
+            let (compressed, v) =
+                #request_ident::decompress(v, None).map_err(servant::ServantError::from)?;
+            if compressed {
+                #accepted.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+            Ok(v)
+        })
+    }
+}
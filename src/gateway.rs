@@ -0,0 +1,301 @@
+// -- gateway.rs --
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::Ident;
+
+// --
+
+// http和jsonrpc feature共用serve_json，dispatch是调用servant的表达式，返回bincode编码的ServantResult；
+// dispatch中的request_bytes是收到的JSON请求的字节数，用于metrics。设置了max_frame_size时，
+// 与bincode和gRPC的请求一样，在解码JSON之前检查请求的大小
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render_gateway(
+    servant_ident: &Ident,
+    trait_ident: &Ident,
+    request_ident: &Ident,
+    fn_ident_vec: &[Ident],
+    fn_ident_camel_vec: &[Ident],
+    output_vec: &[TokenStream2],
+    dispatch: &TokenStream2,
+    name_check: bool,
+    max_frame_size: Option<usize>,
+) -> TokenStream2 {
+    if !cfg!(all(
+        feature = "server",
+        any(feature = "http", feature = "jsonrpc")
+    )) {
+        return TokenStream2::new();
+    }
+    let serve_json = render_serve_json(
+        servant_ident,
+        trait_ident,
+        request_ident,
+        fn_ident_vec,
+        fn_ident_camel_vec,
+        output_vec,
+        dispatch,
+    );
+    let http = if cfg!(feature = "http") {
+        render_http(servant_ident, trait_ident, name_check, max_frame_size)
+    } else {
+        TokenStream2::new()
+    };
+    let jsonrpc = if cfg!(feature = "jsonrpc") {
+        render_jsonrpc(servant_ident, trait_ident, max_frame_size)
+    } else {
+        TokenStream2::new()
+    };
+    quote! {
+        #serve_json
+        #http
+        #jsonrpc
+    }
+}
+
+// 生成servant的serve_json方法：按照方法名和JSON编码的参数构造请求，通过servant原有的dispatch调用，
+// 再把bincode编码的ServantResult转换成JSON
+fn render_serve_json(
+    servant_ident: &Ident,
+    trait_ident: &Ident,
+    request_ident: &Ident,
+    fn_ident_vec: &[Ident],
+    fn_ident_camel_vec: &[Ident],
+    output_vec: &[TokenStream2],
+    dispatch: &TokenStream2,
+) -> TokenStream2 {
+    quote! {
+        impl<S> #servant_ident<S>
+        where
+            S: #trait_ident + 'static,
+        {
+            // None：没有这个方法；Some(Err)：参数错误；Some(Ok)：方法返回的ServantResult
+            #[allow(dead_code)]
+            fn serve_json(
+                &self,
+                method: &str,
+                params: serde_json::Value,
+                request_bytes: u64,
+            ) -> Option<Result<servant::ServantResult<serde_json::Value>, String>> {
+                let variant = match method {
+                    #(stringify!(#fn_ident_vec) => stringify!(#fn_ident_camel_vec),)*
+                    _ => return None,
+                };
+                let params = match params {
+                    serde_json::Value::Null => serde_json::Value::Object(serde_json::Map::new()),
+                    params => params,
+                };
+                let mut request = serde_json::Map::new();
+                request.insert(variant.to_string(), params);
+                let req: #request_ident = match serde_json::from_value(serde_json::Value::Object(request)) {
+                    Ok(req) => req,
+                    Err(e) => return Some(Err(e.to_string())),
+                };
+                let reps = #dispatch;
+                // 返回值不能编码成JSON时，例如key不是字符串的HashMap，作为方法返回的ServantError
+                let reps = match method {
+                    #(
+                        stringify!(#fn_ident_vec) => bincode::deserialize::<servant::ServantResult<#output_vec>>(&reps)
+                            .unwrap_or_else(|e| Err(format!("invalid response: {}", e).into()))
+                            .and_then(|v| {
+                                serde_json::to_value(v).map_err(|e| format!("invalid response: {}", e).into())
+                            }),
+                    )*
+                    _ => unreachable!(),
+                };
+                Some(Ok(reps))
+            }
+        }
+    }
+}
+
+// 生成HTTP的handler：POST /{category}/{object_name}/{method}，body是JSON编码的命名参数
+fn render_http(
+    servant_ident: &Ident,
+    trait_ident: &Ident,
+    name_check: bool,
+    max_frame_size: Option<usize>,
+) -> TokenStream2 {
+    // watch接口只有一个对象，没有名字，不检查object_name
+    let name_check = if name_check {
+        quote! { name != self.name }
+    } else {
+        quote! { false }
+    };
+    // 超过max_frame_size的body不解码，返回413
+    let size_check = match max_frame_size {
+        Some(max) => quote! {
+            if body.len() > #max {
+                return error(413, format!("frame size {} exceeds max_frame_size {}.", body.len(), #max));
+            }
+        },
+        None => TokenStream2::new(),
+    };
+    quote! {
+        impl<S> servant::HttpServant for #servant_ident<S>
+        where
+            S: #trait_ident + 'static,
+        {
+            fn serve_http(&self, method: &str, path: &str, body: &[u8]) -> (u16, Vec<u8>) {
+                let error = |status: u16, msg: String| {
+                    (status, serde_json::to_vec(&serde_json::json!({ "error": msg })).unwrap())
+                };
+                if method != "POST" {
+                    return error(405, format!("method '{}' is not allowed.", method));
+                }
+                let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+                let (category, name, m) = match segments.as_slice() {
+                    [category, name, m] => (*category, *name, *m),
+                    _ => return error(404, format!("path '{}' is not found.", path)),
+                };
+                if category != Self::category() || #name_check {
+                    return error(404, format!("path '{}' is not found.", path));
+                }
+                #size_check
+                let params = if body.is_empty() {
+                    serde_json::Value::Null
+                } else {
+                    match serde_json::from_slice(body) {
+                        Ok(params) => params,
+                        Err(e) => return error(400, e.to_string()),
+                    }
+                };
+                match self.serve_json(m, params, body.len() as u64) {
+                    None => error(404, format!("method '{}' is not found.", m)),
+                    Some(Err(e)) => error(400, e),
+                    Some(Ok(Ok(v))) => (200, serde_json::to_vec(&v).unwrap()),
+                    Some(Ok(Err(e))) => error(500, e.to_string()),
+                }
+            }
+        }
+    }
+}
+
+// 生成JSON-RPC 2.0的adapter：method是`{category}.{method}`，params可以按位置或者按名字，支持batch
+fn render_jsonrpc(
+    servant_ident: &Ident,
+    trait_ident: &Ident,
+    max_frame_size: Option<usize>,
+) -> TokenStream2 {
+    // 超过max_frame_size的请求不解码，整个请求（包括batch）返回id为null的-32600错误
+    let size_check = match max_frame_size {
+        Some(max) => quote! {
+            if req.len() > #max {
+                let reps = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "error": {
+                        "code": -32600,
+                        "message": format!("frame size {} exceeds max_frame_size {}.", req.len(), #max),
+                    },
+                    "id": null,
+                });
+                return Some(serde_json::to_vec(&reps).unwrap());
+            }
+        },
+        None => TokenStream2::new(),
+    };
+    quote! {
+        impl<S> #servant_ident<S>
+        where
+            S: #trait_ident + 'static,
+        {
+            fn jsonrpc_result(
+                &self,
+                req: &serde_json::Value,
+                request_bytes: u64,
+            ) -> Result<serde_json::Value, (i64, String)> {
+                if req.get("jsonrpc").and_then(serde_json::Value::as_str) != Some("2.0") {
+                    return Err((-32600, "invalid request.".to_string()));
+                }
+                let method = req
+                    .get("method")
+                    .and_then(serde_json::Value::as_str)
+                    .ok_or_else(|| (-32600, "method is not a string.".to_string()))?;
+                let not_found = || (-32601, format!("method '{}' is not found.", method));
+                let m = method
+                    .strip_prefix(Self::category())
+                    .and_then(|m| m.strip_prefix('.'))
+                    .ok_or_else(not_found)?;
+                let descriptor = Self::DESCRIPTOR
+                    .methods
+                    .iter()
+                    .find(|d| d.name == m)
+                    .ok_or_else(not_found)?;
+                // 按位置的参数按照方法定义中的顺序转换成按名字的参数
+                let params = match req.get("params") {
+                    None | Some(serde_json::Value::Null) => serde_json::Value::Null,
+                    Some(serde_json::Value::Array(params)) => {
+                        if params.len() != descriptor.params.len() {
+                            return Err((
+                                -32602,
+                                format!("{} params expected, {} given.", descriptor.params.len(), params.len()),
+                            ));
+                        }
+                        serde_json::Value::Object(
+                            descriptor
+                                .params
+                                .iter()
+                                .map(|p| p.name.to_string())
+                                .zip(params.iter().cloned())
+                                .collect(),
+                        )
+                    }
+                    Some(params @ serde_json::Value::Object(_)) => params.clone(),
+                    Some(_) => return Err((-32600, "params is not an array or object.".to_string())),
+                };
+                match self.serve_json(m, params, request_bytes) {
+                    None => Err(not_found()),
+                    Some(Err(e)) => Err((-32602, e)),
+                    Some(Ok(Ok(v))) => Ok(v),
+                    Some(Ok(Err(e))) => Err((-32000, e.to_string())),
+                }
+            }
+            // 没有id的请求是notification，不返回响应；无效的请求总是返回id为null的错误
+            fn jsonrpc_call(&self, req: &serde_json::Value, request_bytes: u64) -> Option<serde_json::Value> {
+                let result = self.jsonrpc_result(req, request_bytes);
+                let id = match (req.get("id"), &result) {
+                    (Some(id), _) => id.clone(),
+                    (None, Err((-32600, _))) => serde_json::Value::Null,
+                    (None, _) => return None,
+                };
+                Some(match result {
+                    Ok(v) => serde_json::json!({ "jsonrpc": "2.0", "result": v, "id": id }),
+                    Err((code, message)) => serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "error": { "code": code, "message": message },
+                        "id": id,
+                    }),
+                })
+            }
+        }
+        impl<S> servant::JsonRpcServant for #servant_ident<S>
+        where
+            S: #trait_ident + 'static,
+        {
+            fn serve_jsonrpc(&self, req: &[u8]) -> Option<Vec<u8>> {
+                #size_check
+                let reps = match serde_json::from_slice(req) {
+                    Ok(serde_json::Value::Array(reqs)) if !reqs.is_empty() => {
+                        // batch中的每个请求记录平均的字节数
+                        let request_bytes = req.len() as u64 / reqs.len() as u64;
+                        let reps: Vec<_> = reqs
+                            .iter()
+                            .filter_map(|req| self.jsonrpc_call(req, request_bytes))
+                            .collect();
+                        if reps.is_empty() {
+                            None
+                        } else {
+                            Some(serde_json::Value::Array(reps))
+                        }
+                    }
+                    Ok(value) => self.jsonrpc_call(&value, req.len() as u64),
+                    Err(e) => Some(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "error": { "code": -32700, "message": e.to_string() },
+                        "id": null,
+                    })),
+                };
+                reps.map(|reps| serde_json::to_vec(&reps).unwrap())
+            }
+        }
+    }
+}
@@ -0,0 +1,515 @@
+// -- idl.rs --
+
+use crate::parse::doc_string;
+use crate::utilities::type_to_string;
+use proc_macro2::TokenStream as TokenStream2;
+use std::{fs, io, path::PathBuf};
+use syn::{
+    Attribute, Data, DeriveInput, Fields, GenericArgument, Meta, NestedMeta, PathArguments, Type,
+};
+
+// --
+
+const IDL_DIR: &str = "servant";
+const TYPES_DIR: &str = "types";
+const WIRE_CODEC: &str = "bincode";
+// serde和标准库中的类型，bincode的编码是确定的；其他的类型都是自定义类型
+const BUILTIN_TYPES: &[&str] = &[
+    "bool",
+    "char",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "f32",
+    "f64",
+    "str",
+    "String",
+    "Vec",
+    "VecDeque",
+    "Option",
+    "Result",
+    "Box",
+    "Rc",
+    "Arc",
+    "HashMap",
+    "BTreeMap",
+    "HashSet",
+    "BTreeSet",
+    "ServantResult",
+    "ServantError",
+];
+
+// --
+
+// 接口的定义，从TraitContext得到，用于导出IDL以及生成其他语言的代码
+#[cfg_attr(test, derive(Debug))]
+pub(crate) struct Interface {
+    pub(crate) kind: String,
+    pub(crate) name: String,
+    pub(crate) doc: String,
+    pub(crate) methods: Vec<Method>,
+}
+
+#[cfg_attr(test, derive(Debug))]
+pub(crate) struct Method {
+    pub(crate) name: String,
+    pub(crate) doc: String,
+    pub(crate) params: Vec<Param>,
+    pub(crate) output: Type,
+}
+
+#[cfg_attr(test, derive(Debug))]
+pub(crate) struct Param {
+    pub(crate) name: String,
+    pub(crate) ty: Type,
+}
+
+impl Interface {
+    pub(crate) fn request_name(&self) -> String {
+        format!("{}Request", self.name)
+    }
+
+    // 请求按照bincode编码，方法在XxxRequest中的序号就是enum variant的序号
+    pub(crate) fn to_json(&self) -> String {
+        let methods = self
+            .methods
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let params = m
+                    .params
+                    .iter()
+                    .map(|p| {
+                        Json::Obj(vec![
+                            ("name", Json::Str(p.name.clone())),
+                            ("type", type_shape(&p.ty)),
+                        ])
+                    })
+                    .collect();
+                Json::Obj(vec![
+                    ("name", Json::Str(m.name.clone())),
+                    ("doc", Json::Str(m.doc.clone())),
+                    ("variant", Json::Num(i)),
+                    ("params", Json::Arr(params)),
+                    ("output", type_shape(&m.output)),
+                ])
+            })
+            .collect();
+        let mut idl = vec![
+            ("kind", Json::Str(self.kind.clone())),
+            ("name", Json::Str(self.name.clone())),
+            ("doc", Json::Str(self.doc.clone())),
+            ("codec", Json::Str(WIRE_CODEC.to_string())),
+            ("request", Json::Str(self.request_name())),
+        ];
+        // invoke接口的请求在XxxFrame中发送，Call是一个请求，Batch是多个请求
+        if self.kind == "invoke" {
+            idl.push(("frame", self.frame()));
+        }
+        idl.push(("methods", Json::Arr(methods)));
+        let idl = Json::Obj(idl);
+
+        let mut json = String::new();
+        idl.write(0, &mut json);
+        json.push('\n');
+        json
+    }
+
+    fn frame(&self) -> Json {
+        let request = shape(&self.request_name(), Vec::new(), None);
+        let bytes = shape("Vec", vec![shape("u8", Vec::new(), None)], None);
+        Json::Obj(vec![
+            ("name", Json::Str(format!("{}Frame", self.name))),
+            (
+                "variants",
+                Json::Arr(vec![
+                    Json::Obj(vec![
+                        ("name", Json::Str("Call".to_string())),
+                        ("variant", Json::Num(0)),
+                        ("type", request.clone()),
+                        ("reply", Json::Str("ServantResult<output>".to_string())),
+                    ]),
+                    Json::Obj(vec![
+                        ("name", Json::Str("Batch".to_string())),
+                        ("variant", Json::Num(1)),
+                        ("type", shape("Vec", vec![request], None)),
+                        ("reply", shape("Vec", vec![bytes], None)),
+                    ]),
+                ]),
+            ),
+        ])
+    }
+}
+
+// --
+
+// idl_type标记的自定义类型，按照bincode的编码导出字段或者variant的类型结构
+pub(crate) fn type_to_json(item: &DeriveInput) -> String {
+    let fields_json = |fields: &Fields| {
+        let fields = fields
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| !serde_skip(&f.attrs))
+            .map(|(i, f)| {
+                let name = f
+                    .ident
+                    .as_ref()
+                    .map_or_else(|| i.to_string(), |i| i.to_string());
+                Json::Obj(vec![("name", Json::Str(name)), ("type", type_shape(&f.ty))])
+            })
+            .collect();
+        Json::Arr(fields)
+    };
+    let (kind, body) = match &item.data {
+        Data::Struct(d) => ("struct", ("fields", fields_json(&d.fields))),
+        Data::Enum(d) => {
+            let variants = d
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    Json::Obj(vec![
+                        ("name", Json::Str(v.ident.to_string())),
+                        ("variant", Json::Num(i)),
+                        ("fields", fields_json(&v.fields)),
+                    ])
+                })
+                .collect();
+            ("enum", ("variants", Json::Arr(variants)))
+        }
+        // serde不支持union，lib.rs中已经报告错误
+        Data::Union(_) => unreachable!(),
+    };
+    let obj = vec![
+        ("kind", Json::Str(kind.to_string())),
+        ("name", Json::Str(item.ident.to_string())),
+        ("doc", Json::Str(doc_string(&item.attrs))),
+        body,
+    ];
+
+    let mut json = String::new();
+    Json::Obj(obj).write(0, &mut json);
+    json.push('\n');
+    json
+}
+
+pub(crate) fn type_file_name(item: &DeriveInput) -> String {
+    format!("{}/{}.json", TYPES_DIR, item.ident)
+}
+
+// #[serde(skip)]的字段不参与编码
+fn serde_skip(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident("serde"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::List(l)) => Some(l.nested),
+            _ => None,
+        })
+        .flatten()
+        .any(|n| matches!(n, NestedMeta::Meta(Meta::Path(p)) if p.is_ident("skip")))
+}
+
+// --
+
+// 只使用接口的定义可以确定的类型结构：路径、泛型参数、tuple、数组等；
+// 自定义类型的结构在idl_type导出的types/{name}.json中，这里使用ref引用
+fn type_shape(ty: &Type) -> Json {
+    match ty {
+        Type::Path(p) => {
+            let name = p
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect::<Vec<_>>()
+                .join("::");
+            let args: Vec<_> = match &p.path.segments.last().unwrap().arguments {
+                PathArguments::AngleBracketed(a) => a
+                    .args
+                    .iter()
+                    .filter_map(|a| {
+                        if let GenericArgument::Type(t) = a {
+                            Some(type_shape(t))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let last = p.path.segments.last().unwrap().ident.to_string();
+            if BUILTIN_TYPES.contains(&last.as_str()) {
+                shape(&name, args, None)
+            } else {
+                let mut custom = shape(&name, args, None);
+                if let Json::Obj(obj) = &mut custom {
+                    obj.push(("ref", Json::Str(format!("{}/{}.json", TYPES_DIR, last))));
+                }
+                custom
+            }
+        }
+        Type::Reference(r) => type_shape(&r.elem),
+        Type::Paren(p) => type_shape(&p.elem),
+        Type::Group(g) => type_shape(&g.elem),
+        Type::Tuple(t) if t.elems.is_empty() => shape("()", Vec::new(), None),
+        Type::Tuple(t) => shape("tuple", t.elems.iter().map(type_shape).collect(), None),
+        Type::Slice(s) => shape("slice", vec![type_shape(&s.elem)], None),
+        Type::Array(a) => {
+            let len = &a.len;
+            let len = type_to_string(&quote! {#len}.to_string());
+            shape("array", vec![type_shape(&a.elem)], Some(len))
+        }
+        _ => shape(&type_to_string(&quote! {#ty}.to_string()), Vec::new(), None),
+    }
+}
+
+fn shape(name: &str, args: Vec<Json>, len: Option<String>) -> Json {
+    let mut obj = vec![("type", Json::Str(name.to_string()))];
+    if !args.is_empty() {
+        obj.push(("args", Json::Arr(args)));
+    }
+    if let Some(len) = len {
+        obj.push(("len", Json::Str(len)));
+    }
+    Json::Obj(obj)
+}
+
+// --
+
+// 导出的IDL按照固定的顺序和缩进输出，方便在code review中比较
+#[derive(Clone)]
+enum Json {
+    Str(String),
+    Num(usize),
+    Arr(Vec<Json>),
+    Obj(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn write(&self, indent: usize, out: &mut String) {
+        let pad = "  ".repeat(indent + 1);
+        match self {
+            Json::Str(s) => write_str(s, out),
+            Json::Num(n) => out.push_str(&n.to_string()),
+            Json::Arr(v) if v.is_empty() => out.push_str("[]"),
+            Json::Arr(v) => {
+                out.push_str("[\n");
+                for (i, j) in v.iter().enumerate() {
+                    out.push_str(&pad);
+                    j.write(indent + 1, out);
+                    out.push_str(if i + 1 < v.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(indent));
+                out.push(']');
+            }
+            Json::Obj(v) => {
+                out.push_str("{\n");
+                for (i, (k, j)) in v.iter().enumerate() {
+                    out.push_str(&pad);
+                    write_str(k, out);
+                    out.push_str(": ");
+                    j.write(indent + 1, out);
+                    out.push_str(if i + 1 < v.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(indent));
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_str(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// OUT_DIR是使用接口的crate的编译输出目录，只有crate有build.rs时才会设置
+pub(crate) fn export(file_name: &str, content: &str) -> io::Result<PathBuf> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "OUT_DIR is not set, build.rs is required to export interface.",
+        )
+    })?;
+    let path = PathBuf::from(out_dir).join(IDL_DIR).join(file_name);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+// 导出失败时，在使用接口的地方报告编译错误
+pub(crate) fn render_export_error(e: io::Error) -> TokenStream2 {
+    let msg = format!("failed to export interface: {}", e);
+    quote! {
+        compile_error!(#msg);
+    }
+}
+
+// --
+
+#[cfg(test)]
+mod tests {
+    use super::type_to_json;
+    use crate::parse::TraitContext;
+    use serde_json::{json, Value};
+
+    fn interface_json(kind: &str, trait_context: TraitContext) -> Value {
+        serde_json::from_str(&trait_context.interface(kind).to_json()).unwrap()
+    }
+
+    #[test]
+    fn test_interface_json() {
+        let idl = interface_json(
+            "invoke",
+            syn::parse_quote! {
+                /// hello "world"
+                pub trait Hello {
+                    fn hello(&self, n: i32, names: Vec<String>) -> Option<String>;
+                    fn bye(&mut self, pair: (u8, [u8; 4]), p: geo::Point);
+                }
+            },
+        );
+        assert_eq!(idl["kind"], "invoke");
+        assert_eq!(idl["name"], "Hello");
+        assert_eq!(idl["doc"], "hello \"world\"");
+        assert_eq!(idl["codec"], "bincode");
+        assert_eq!(idl["request"], "HelloRequest");
+        assert_eq!(
+            idl["frame"],
+            json!({
+                "name": "HelloFrame",
+                "variants": [
+                    {
+                        "name": "Call",
+                        "variant": 0,
+                        "type": { "type": "HelloRequest" },
+                        "reply": "ServantResult<output>"
+                    },
+                    {
+                        "name": "Batch",
+                        "variant": 1,
+                        "type": { "type": "Vec", "args": [{ "type": "HelloRequest" }] },
+                        "reply": { "type": "Vec", "args": [{ "type": "Vec", "args": [{ "type": "u8" }] }] }
+                    }
+                ]
+            })
+        );
+        assert_eq!(
+            idl["methods"][0],
+            json!({
+                "name": "hello",
+                "doc": "",
+                "variant": 0,
+                "params": [
+                    { "name": "n", "type": { "type": "i32" } },
+                    { "name": "names", "type": { "type": "Vec", "args": [{ "type": "String" }] } }
+                ],
+                "output": { "type": "Option", "args": [{ "type": "String" }] }
+            })
+        );
+        assert_eq!(idl["methods"][1]["variant"], 1);
+        assert_eq!(
+            idl["methods"][1]["params"][0]["type"],
+            json!({
+                "type": "tuple",
+                "args": [
+                    { "type": "u8" },
+                    { "type": "array", "args": [{ "type": "u8" }], "len": "4" }
+                ]
+            })
+        );
+        assert_eq!(
+            idl["methods"][1]["params"][1]["type"],
+            json!({ "type": "geo::Point", "ref": "types/Point.json" })
+        );
+        assert_eq!(idl["methods"][1]["output"], json!({ "type": "()" }));
+    }
+
+    #[test]
+    fn test_interface_json_without_frame() {
+        let idl = interface_json(
+            "watch",
+            syn::parse_quote! {
+                pub trait Dog {
+                    fn name(&self) -> String;
+                }
+            },
+        );
+        assert_eq!(idl["kind"], "watch");
+        assert!(idl.get("frame").is_none());
+        assert_eq!(idl["methods"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_type_json() {
+        let item: syn::DeriveInput = syn::parse_quote! {
+            /// a point
+            struct Point {
+                x: i32,
+                #[serde(skip)]
+                cache: u64,
+                tags: Vec<Tag>,
+            }
+        };
+        let v: Value = serde_json::from_str(&type_to_json(&item)).unwrap();
+        assert_eq!(
+            v,
+            json!({
+                "kind": "struct",
+                "name": "Point",
+                "doc": "a point",
+                "fields": [
+                    { "name": "x", "type": { "type": "i32" } },
+                    { "name": "tags", "type": { "type": "Vec", "args": [{ "type": "Tag", "ref": "types/Tag.json" }] } }
+                ]
+            })
+        );
+
+        let item: syn::DeriveInput = syn::parse_quote! {
+            enum Shape {
+                Empty,
+                Circle(f64),
+                Rect { w: u32, h: u32 },
+            }
+        };
+        let v: Value = serde_json::from_str(&type_to_json(&item)).unwrap();
+        assert_eq!(v["kind"], "enum");
+        assert_eq!(
+            v["variants"],
+            json!([
+                { "name": "Empty", "variant": 0, "fields": [] },
+                { "name": "Circle", "variant": 1, "fields": [{ "name": "0", "type": { "type": "f64" } }] },
+                {
+                    "name": "Rect",
+                    "variant": 2,
+                    "fields": [
+                        { "name": "w", "type": { "type": "u32" } },
+                        { "name": "h", "type": { "type": "u32" } }
+                    ]
+                }
+            ])
+        );
+    }
+}
@@ -45,7 +45,7 @@
 //!
 //! 在定义接口时，可以使用不同的attributes定制化自动生成的代码。每种接口能使用的attributes是不同的，具体可以参考前面代码中的举例。
 //!
-//! 有四种attributes可以使用：
+//! 有七种attributes可以使用：
 //!
 //! 1. proxy：类型是&str，定义客户端使用的proxy的名称。缺省生成的名称是在trait name后直接增加Proxy，就像如下代码一样：
 //!
@@ -65,16 +65,124 @@
 //!
 //! 4. callback：类型是Bool，如果是true，客户端可以使用异步调用；如果是false，不支持异步调用。
 //!
+//! 5. catch_unwind：类型是Bool，缺省是true，服务端调用接口方法时捕获panic。invoke和watch接口将panic转换成ServantError返回给客户端，report和notify接口将错误交给officer或receiver的`on_error(f)`设置的函数处理，没有设置时tracing feature输出error event，servant在panic之后仍然可以继续提供服务；如果是false，不捕获panic。
+//!
+//! 6. retry、retry_backoff_ms：类型是整数，只用于invoke接口，缺省分别是3和100。使用`#[servant(idempotent)]`标记的方法是幂等的，发生传输错误（`ServantError::is_transport()`）时，proxy最多重试retry次，第一次重试前等待retry_backoff_ms毫秒，之后每次等待的时间加倍；方法返回的ServantError不会重试，没有标记的方法也不会重试。`idempotent`只能用于invoke接口的方法。
+//!
+//! 7. grpc：类型是Bool，只用于invoke接口，缺省是false。如果是true，在服务端生成`{trait name}Grpc`，它是gRPC的adapter，
+//!    与servant共享同一个实现类的对象；`{trait name}Grpc::PROTO`是对应的.proto定义，每个方法对应一个rpc，protobuf中没有
+//!    对应类型的参数和返回值使用bincode编码成bytes。gRPC服务器调用`GrpcService::call(ctx, method, req)`，ctx由请求的
+//!    metadata转换而来，与servant一样传给实现类的方法。解码后的请求通过servant的dispatch调用，与servant收到的请求一样
+//!    检查max_frame_size、rate_limit和validate，并且记录tracing和metrics。
+//!
+//! 接口中的方法也可以使用`#[servant(cache_ttl_ms = ...)]`，用于invoke和watch接口。proxy以方法名和序列化后的请求（即方法的参数）作为key，缓存方法成功的结果，在cache_ttl_ms毫秒内，相同参数的调用直接返回缓存的结果。proxy最多缓存256个结果，插入时先清除过期的结果，缓存满时淘汰最久没有使用的结果。proxy生成`invalidate_cache()`和`invalidate_xxx()`方法，分别清除全部的缓存和某个方法的缓存，接口的方法不能使用这些名字；`context_mut()`之后proxy使用新的缓存。report和notify接口的方法不能使用cache_ttl_ms。
+//!
+//! 接口方法的参数必须是标识符，例如`n: i32`，不能使用`mut n`、`_`或者解构的模式，这些名字用于生成的请求和各种客户端。
+//!
+//! 接口方法的参数可以使用`#[servant(redact)]`，例如密码等，tracing feature生成的span中只记录`<redacted>`，不记录参数的值。
+//!
+//! invoke接口的方法可以使用`#[servant(rate_limit = "100/s")]`，单位是s、m或者h。servant在调用方法之前，按照`servant::Context::peer()`中对端的IP地址分别计数，每个地址在单位时间内最多调用指定的次数，没有对端地址的请求共用一个计数；每个servant对象有自己的`servant::RateLimiter`，只保留有限个计数，过期的计数会被清除；超过时不调用方法，返回`servant::ServantError::throttled(...)`。proxy通过`retry_after_ms()`识别这个错误，等待服务端返回的时间后重试，最多retry次。report、watch和notify接口的方法不能使用rate_limit。
+//!
+//! 四种接口都可以使用`max_frame_size = ...`属性，单位是字节。servant反序列化请求时，超过这个大小的请求直接返回错误，并且按照这个大小限制bincode的解码，请求中的长度不会导致分配过多的内存；invoke和watch接口的proxy也不发送超过这个大小的请求；http和jsonrpc feature在解码JSON之前检查body的大小，超过时HTTP返回413，JSON-RPC返回id为null的-32600错误。解码失败的请求不再panic，invoke和watch接口返回`servant::ServantError`，report和notify接口把错误交给`on_error(f)`设置的函数处理后丢弃，`stream = true`生成的event sender只在tracing feature输出error event。
+//!
+//! 接口的方法可以使用`#[servant(validate = "path::to::fn")]`，servant在调用实现类的方法之前，以解码后参数的引用调用这个函数，例如`fn put(key: &str, value: &[u8]) -> servant::ServantResult<()>`；返回Err时不调用方法，invoke和watch接口把这个错误返回给proxy，report和notify接口与panic一样，把错误交给`on_error(f)`设置的函数处理。
+//!
+//! invoke接口可以使用`compress = "zstd"`或者`compress = "lz4"`属性，方法也可以使用`#[servant(compress = ...)]`，优先于接口的属性；`compress_threshold = ...`是压缩的阈值，单位是字节，缺省是4096。压缩之后变小的数据，在数据之前增加一个表示压缩算法的flag字节，没有压缩的数据是原来的bincode数据，没有flag。proxy在`servant::Context`中设置`accept_compress`，servant收到压缩的请求或者有accept_compress的请求时，才按照方法的compress压缩响应；proxy收到压缩的响应之后，才压缩超过阈值的请求。所以没有压缩的proxy和servant都可以与支持compress的对方通信。批量调用使用接口的compress，接口没有时使用第一个compress的方法。生成的代码直接使用`zstd`或者`lz4_flex`，使用哪个算法就需要依赖哪个crate。watch、report和notify接口不能使用compress。
+//!
+//! ### 接口的描述
+//!
+//! 每种接口生成的servant和proxy都有`DESCRIPTOR`常量，类型是`servant::InterfaceDescriptor`，包括接口的
+//! 类型、名称、文档注释，以及每个方法的名称、参数的名称和类型、返回值的类型和文档注释，可以在运行时
+//! 列出接口提供的方法。
+//!
+//! ```ignore
+//! for m in HelloProxy::DESCRIPTOR.methods {
+//!     println!("{}: {:?} -> {}", m.name, m.params, m.output);
+//! }
+//! ```
+//!
+//! ### 依赖的servant版本
+//!
+//! 生成的代码使用servant库中的类型和函数，两个库的版本同时发布，本库的0.2.0版本需要servant 0.2.0，servant
+//! 需要先于本库发布。Cargo.toml中固定了servant的版本；运行本库的测试时，servant 0.2.0的代码需要在同一级目录
+//! `../servant`中，否则cargo报告不能读取`../servant/Cargo.toml`。与0.1相比，`servant::Servant`、
+//! `servant::WatchServant`和`servant::ReportServant`的`serve`改为`&self`，servant在内部使用RwLock，可以并发地
+//! 处理请求。生成的代码用到的servant API如下：
+//!
+//! * 所有接口：`Context`、`Oid`、`Terminal`、`ServantError`、`ServantResult`、`InterfaceDescriptor`等描述接口的
+//!   类型，以及`Servant`、`WatchServant`、`ReportServant`和`NotifyServant`；测试中使用`Terminal::loopback`，
+//!   不经过连接，把proxy发出的`servant::Loopback`请求交给指定的函数处理。
+//!
+//! * invoke接口：`CancelGuard`、`Context::cancel`、`Context::is_cancelled`（Context的clone共享取消的状态）和
+//!   `sleep`，idempotent使用`ServantError::is_transport`；继承和compose使用`CompositeServant`；rate_limit使用
+//!   `Context::peer`、`RateLimiter`（`Default`和`acquire(method, limit, period_ms, ip)`）、
+//!   `ServantError::throttled`和`ServantError::retry_after_ms`；compress使用`Context::accept_compress`和
+//!   `Context::set_accept_compress`。
+//!
+//! * report接口：`delivery = "at_least_once"`使用`Outbox`（包括`origin`和`last_seq`）和`Terminal::report_acked`，
+//!   以及futures。
+//!
+//! * notify接口：`AdapterRegister::send_to`（返回`ServantResult<()>`，没有连接时返回transport错误）、`Recipient`、
+//!   `ClientId`和`Terminal::subscribe`；测试中使用`AdapterRegister::with_sender`，不经过连接，直接得到notifier发送
+//!   的通知。`stream = true`生成的代码不使用servant，而是直接使用futures，使用接口的crate需要依赖futures。
+//!
+//! * features：grpc使用`GrpcService`（`call`的第一个参数是`Option<Context>`），http使用`HttpServant`，jsonrpc使用
+//!   `JsonRpcServant`，tracing使用`new_trace_id`，metrics使用`servant::metrics`模块；servant同时导出本库的`idl_type`。
+//!
+//! * 本库的测试：还使用`Context::set_peer`、`Context::set_client_id`、`Context::set_trace_id`、`Outbox::memory`、
+//!   `ServantError::transport`和`servant::metrics::registry`，servant的`full` feature包括以上所有的API。
+//!
 //! ### Cargo.toml文件中可以使用的Features说明
 //!
 //! * client: 引入客户端的代码。
 //!
 //! * adapter: 引入服务端的代码。
 //!
+//! * idl: 将每个接口的定义导出成JSON格式的IDL文件，写到`$OUT_DIR/servant/{trait name}.json`中，包括接口的类型、
+//!   每个方法的参数和返回值的类型结构，以及在bincode编码中方法对应的enum variant序号；invoke接口还包括请求外层的
+//!   `XxxFrame`，Call的序号是0，Batch的序号是1。自定义类型使用[`idl_type`]导出结构，IDL中通过ref引用
+//!   `types/{type name}.json`。使用接口的crate必须有build.rs，否则没有OUT_DIR，编译时会报错。invoke接口同时导出
+//!   `{trait name}.proto`。不同模块中有同名的接口时，使用接口的`idl_name = "..."`属性指定导出的文件名，否则后导出的
+//!   文件会覆盖先导出的文件。
+//!
+//! * typescript: 为invoke和watch接口生成TypeScript的客户端代码，写到`$OUT_DIR/servant/{trait name}.ts`中，包括
+//!   请求和返回值的类型，以及通过JSON调用接口方法的proxy。所有接口共用的`servant.ts`中定义了Transport，由使用者
+//!   实现，将JSON编码的命名参数POST到http feature的路径，返回状态码和body；状态码是200时body是返回值，否则是
+//!   `{"error": "..."}`，proxy抛出ServantError。i64、u64、i128、u128、isize和usize映射为bigint，解码时不损失精度。
+//!   参数或者方法的名字是JavaScript的保留字时，生成的代码在名字后面加上下划线，例如`delete_`，JSON中仍然使用原来的
+//!   名字。
+//!
+//! * python: 为invoke和watch接口生成Python的客户端代码，写到`$OUT_DIR/servant/{trait name}.py`中，包括请求的编码、
+//!   响应的解码，以及调用接口方法的proxy。所有接口共用的`servant.py`按照bincode缺省的配置编码，其中的Transport
+//!   由使用者实现；自定义类型需要使用`servant.register`注册编码和解码函数。参数或者方法的名字是Python的关键字时，
+//!   生成的代码在名字后面加上下划线，例如`from_`。
+//!
+//! * http: 为invoke和watch接口的servant实现`servant::HttpServant`，处理`POST /{category}/{object_name}/{method}`，
+//!   body是JSON编码的命名参数，例如`{"n": 1}`，返回JSON编码的结果；出错时返回`{"error": "..."}`以及对应的状态码，
+//!   方法返回ServantError或者返回值不能编码成JSON时状态码是500。
+//!   watch接口没有对象的名字，object_name可以是任意的。使用接口的crate需要依赖serde_json。
+//!
+//! * jsonrpc: 为invoke和watch接口的servant实现`servant::JsonRpcServant`，处理JSON-RPC 2.0的请求。method是
+//!   `{category}.{method}`，params可以按位置或者按名字，支持batch和notification；错误使用JSON-RPC定义的错误码，
+//!   方法返回的ServantError或者返回值不能编码成JSON时错误码是-32000。使用接口的crate需要依赖serde_json。
+//!
+//! * tracing: 生成的proxy和servant在调用接口方法时创建tracing的span，名字是`{category}.{method}`，记录
+//!   `servant.category`、`servant.method`和每个参数的值，参数的类型必须实现Debug；调用结束时输出event，失败时包括
+//!   错误。invoke接口的proxy在Context中没有trace id时生成新的，随Context发送给服务端，servant的span中在
+//!   `servant.trace_id`记录同一个trace id。生成的字段使用`servant.`前缀，生成的代码中的局部变量使用`__servant_`
+//!   前缀，不会与同名的参数冲突。使用接口的crate需要依赖tracing。
+//!
+//! * metrics: 生成的servant和proxy在调用接口方法时，通过`servant::metrics::record`记录调用的次数、是否失败、请求和
+//!   响应的字节数以及耗时，按照接口和方法统计。被rate_limit或validate拒绝的调用、notifier发送失败的通知都记录为
+//!   失败的调用；servant记录的请求字节数是收到的请求的长度，batch中的每个请求记录平均值。Recorder可以通过
+//!   `servant::metrics::set_recorder`替换；缺省是内存中的`servant::metrics::registry()`，其中的`dump()`返回每个
+//!   方法的统计，可以作为watch接口方法的返回值导出。
+//!
 //! [`invoke_interface`]: attr.invoke_interface.html
 //! [`watch_interface`]: attr.watch_interface.html
 //! [`report_interface`]: attr.report_interface.html
 //! [`notify_interface`]: attr.notify_interface.html
+//! [`idl_type`]: attr.idl_type.html
 
 // --
 
@@ -91,7 +199,15 @@ use proc_macro::TokenStream;
 
 // --
 
+mod compress;
+mod gateway;
+mod idl;
+mod metrics;
 mod parse;
+mod protobuf;
+mod python;
+mod trace;
+mod typescript;
 mod utilities;
 
 // --
@@ -111,6 +227,37 @@ mod utilities;
 /// 接口中方法的第一个参数必须是&self或&mut self，因为接口在服务端都是按照对象提供服务的，每个接口
 /// 可以有不同的实现类，每个类也可以有不同名字的对象分别提供服务。
 ///
+/// 生成的servant使用RwLock包装实现类的对象，&self的方法在读锁下调用，可以并发执行；&mut self的
+/// 方法在写锁下调用，独占执行。
+///
+/// proxy的每次调用都在Context中带有请求的ID，如果在调用完成之前drop了proxy方法返回的future，
+/// proxy会通知服务端取消这个请求。servant只在分发请求之前检查一次`ctx.is_cancelled()`，已经取消的
+/// 请求不会再调用实现类的方法，返回`ServantResult<()>`的Err，proxy按照任何返回类型都解码为Err；
+/// 方法执行期间取消的请求，需要实现类在方法中自己检查`ctx.is_cancelled()`。
+///
+/// proxy的`batch()`方法生成批量调用的builder，多个方法调用合并成一个请求发送，servant按照调用的顺序
+/// 处理，返回每个方法的结果；不能解码的结果是ServantError。proxy和builder中已经有new、category、
+/// context_mut、terminal、batch、call和invalidate_cache方法，接口的方法不能使用这些名字。
+/// proxy发送的请求外层都是`XxxFrame`：单个的调用是`XxxFrame::Call`，批量的调用是`XxxFrame::Batch`；
+/// 0.1版本直接发送`XxxRequest`，与0.1版本生成的servant和proxy不兼容。
+///
+/// ```ignore
+/// let mut batch = proxy.batch();
+/// batch.hello(1).hello(2);
+/// for reps in batch.call().await? {
+///     if let HelloResponse::Hello(Ok(s)) = reps {
+///         println!("{}", s);
+///     }
+/// }
+/// ```
+///
+/// 接口可以继承其他的invoke接口，例如`#[servant::invoke_interface(extends = "Hello")]`和
+/// `trait Admin: Hello`。extends中的父接口必须也是supertrait，其他的supertrait只是普通的约束；
+/// 父接口使用缺省的proxy名称，并且定义在当前模块或者上层模块中，多个父接口使用逗号分隔。这时
+/// servant同时实现CompositeServant，按照category分发接口自己和所有父接口的请求；proxy通过
+/// Deref和DerefMut直接调用第一个父接口的方法，使用proxy自己的Context，还生成`as_hello()`等方法，
+/// 得到访问同一个servant的父接口的proxy。
+///
 /// # Example
 /// ```
 /// #[servant::invoke_interface]
@@ -122,6 +269,12 @@ mod utilities;
 pub fn invoke_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
     let attributes = parse_macro_input!(attr as parse::InvokeInterfaceAttributes);
     let trait_context = parse_macro_input!(input as parse::TraitContext);
+    if let Err(e) = trait_context
+        .check("invoke")
+        .and_then(|_| trait_context.check_extends(&attributes))
+    {
+        return e.to_compile_error().into();
+    }
     trait_context.render_invoke_interface(&attributes)
 }
 
@@ -157,6 +310,9 @@ pub fn invoke_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
 pub fn watch_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
     let attributes = parse_macro_input!(attr as parse::WatchInterfaceAttributes);
     let trait_context = parse_macro_input!(input as parse::TraitContext);
+    if let Err(e) = trait_context.check("watch") {
+        return e.to_compile_error().into();
+    }
     trait_context.render_watch_interface(&attributes)
 }
 
@@ -173,6 +329,27 @@ pub fn watch_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
 /// 在客户端会生成proxy，自动实现了该trait的方法，可以通过Terminal的proxy方
 /// 法，生成这个proxy，调用接口的方法，向服务端请求服务。
 ///
+/// 缺省情况下，报告发送之后不需要确认。如果使用`delivery = "at_least_once"`，staff为每个报告分配
+/// 序号，先放入有界的outbox（容量由`outbox_capacity`指定，缺省是1024），收到officer的确认之后
+/// 才从outbox中删除。staff的方法在报告放入outbox之后就返回Ok，只有outbox满时返回错误；发送失败的报告
+/// 保留在outbox中，之后发送报告或者调用`flush()`时按照序号重新发送。staff不会自动重试，连接恢复之后
+/// 需要调用`flush()`，`flush()`在outbox为空时返回Ok，否则返回发送失败的错误。`with_outbox`可以
+/// 指定保存在磁盘上的outbox，staff使用outbox的origin作为自己的标识，并且从outbox中使用过的最大序号
+/// 之后继续分配序号，所以一个outbox只能用于一个staff（以及clone出来的staff）。clone的staff共享序号，
+/// 依次调用`flush()`，按照序号发送报告。officer为每个origin记录收到的最大序号和之前128个序号的窗口，
+/// 丢弃重复收到的报告，最多记录1024个origin，超过时淘汰最久没有报告的origin。生成的staff使用
+/// `futures::lock::Mutex`，使用`at_least_once`的crate需要依赖futures。
+///
+/// staff的`batch()`方法生成批量发送的builder，多个方法调用合并成一个报告发送，officer按照调用的
+/// 顺序处理。staff发送的报告外层都是`XxxFrame`：单个的报告是`XxxFrame::Report`，批量的报告是
+/// `XxxFrame::Batch`；0.1版本直接发送`XxxRequest`，与0.1版本生成的officer和staff不兼容。
+///
+/// ```ignore
+/// let mut batch = staff.batch();
+/// batch.report("a".to_string()).report("b".to_string());
+/// batch.send().await?;
+/// ```
+///
 /// # Notice
 /// 接口中方法的第一个参数必须是&self或&mut self，因为接口在服务端都是按照对象提供服务的，每个接口
 /// 可以有不同的实现类，每个类也可以有不同名字的对象分别提供服务。
@@ -190,6 +367,9 @@ pub fn watch_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
 pub fn report_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
     let attributes = parse_macro_input!(attr as parse::ReportInterfaceAttributes);
     let trait_context = parse_macro_input!(input as parse::TraitContext);
+    if let Err(e) = trait_context.check("report") {
+        return e.to_compile_error().into();
+    }
     trait_context.render_report_interface(&attributes)
 }
 
@@ -203,7 +383,23 @@ pub fn report_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
 /// 在客户端会生成同名的trait，客户端要实现这个trait，在每个方法中接收并处理来自服务端的通知。
 /// 只有将该实现添加到Terminal中，客户端才能收到并处理服务器端的通知。
 ///
-/// 在服务端会生成notifier，自动实现了该trait的方法。在服务端调用notifier的方法，向客户端发送通知。
+/// 在服务端会生成notifier，自动实现了该trait的方法。在服务端调用notifier的方法，向客户端发送通知，
+/// 返回`ServantResult<()>`，没有连接等发送失败时返回Err。
+/// notifier缺省向所有连接的客户端发送通知，通过`to(client_id)`或`to_group(name)`生成新的notifier，
+/// 只向指定的客户端或客户端组发送通知。
+///
+/// 在客户端会生成subscription，客户端可以只订阅接口中的部分方法，服务端发送通知时，跳过没有订阅
+/// 该方法的客户端。没有订阅过的客户端，接收所有的通知。notifier和subscription中已经有new、to、
+/// to_group、all和subscribe方法，接口的方法不能使用这些名字。
+///
+/// ```ignore
+/// DatetimeSubscription::new(&terminal).date().subscribe().await?;
+/// ```
+///
+/// 如果使用`stream = true`，在客户端还会生成`XxxEvent`枚举和`XxxStream`，每个通知方法对应一个
+/// event。将`XxxStream::channel()`返回的`XxxEventSender`添加到Terminal中，就可以通过`XxxStream`
+/// 异步地接收通知，不需要实现trait。生成的代码使用`futures::channel::mpsc`和`futures::Stream`，
+/// 使用`stream = true`的crate需要依赖futures。
 ///
 /// # Notice
 /// 接口中方法的第一个参数必须是&self或&mut self，因为接口在服务端都是按照对象提供服务的。
@@ -221,5 +417,74 @@ pub fn report_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
 pub fn notify_interface(attr: TokenStream, input: TokenStream) -> TokenStream {
     let attributes = parse_macro_input!(attr as parse::NotifyInterfaceAttributes);
     let trait_context = parse_macro_input!(input as parse::TraitContext);
+    if let Err(e) = trait_context.check("notify") {
+        return e.to_compile_error().into();
+    }
     trait_context.render_notify_interface(&attributes)
 }
+
+/// 将多个invoke接口组合成一个servant，这些接口共享同一个实现类的对象。
+///
+/// 在服务端会为struct生成servant，名称是在struct name后直接增加Servant。这个servant使用同一个名
+/// 字，按照category分发每个接口的请求，只需要添加到ServantRegister中一次。客户端仍然使用每个接口
+/// 的proxy访问这个servant。
+///
+/// # Notice
+/// 组合的接口必须是invoke接口，并且定义在当前模块或者上层模块中。struct不能是泛型的。
+///
+/// # Example
+/// ```ignore
+/// #[servant::compose(Hello, Admin)]
+/// pub struct Entity {
+///     count: usize,
+/// }
+///
+/// let servant = EntityServant::new("entity1", Entity { count: 0 });
+/// ```
+#[proc_macro_attribute]
+pub fn compose(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let attributes = parse_macro_input!(attr as parse::ComposeAttributes);
+    let compose_context = parse_macro_input!(input as parse::ComposeContext);
+    compose_context.render_compose(&attributes)
+}
+
+/// 导出接口中使用的自定义类型的结构。
+///
+/// 打开idl feature时，将struct或者enum的字段、variant按照bincode编码的顺序导出到
+/// `$OUT_DIR/servant/types/{type name}.json`中，接口的IDL中使用这个类型的地方通过ref引用这个文件；
+/// `#[serde(skip)]`的字段不导出。没有打开idl feature时，不生成任何代码。
+///
+/// # Example
+/// ```ignore
+/// #[servant::idl_type]
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// pub struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn idl_type(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(input as syn::DeriveInput);
+    if !attr.is_empty() {
+        let e = syn::Error::new(item.ident.span(), "idl_type has no attribute.");
+        return e.to_compile_error().into();
+    }
+    if let syn::Data::Union(_) = item.data {
+        let e = syn::Error::new(item.ident.span(), "union is not supported by serde.");
+        return e.to_compile_error().into();
+    }
+    let output_export = if cfg!(feature = "idl") {
+        match idl::export(&idl::type_file_name(&item), &idl::type_to_json(&item)) {
+            Ok(_) => proc_macro2::TokenStream::new(),
+            Err(e) => idl::render_export_error(e),
+        }
+    } else {
+        proc_macro2::TokenStream::new()
+    };
+    let output = quote! {
+        #item
+        #output_export
+    };
+    output.into()
+}
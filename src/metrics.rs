@@ -0,0 +1,83 @@
+// -- metrics.rs --
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::Ident;
+
+// --
+
+// 生成的servant和proxy在调用接口方法时，通过servant::metrics::record把调用次数、是否成功、请求和响应的字节数
+// 以及耗时交给当前的Recorder；没有metrics feature时什么也不生成
+
+// 开始计时，并记录请求的字节数；局部变量使用__servant_前缀，不会遮住同名的参数
+pub(crate) fn render_start(request_bytes: &TokenStream2) -> TokenStream2 {
+    if cfg!(feature = "metrics") {
+        quote! {
+            let __servant_metrics_request_bytes: u64 = #request_bytes;
+            let __servant_metrics_start = std::time::Instant::now();
+        }
+    } else {
+        TokenStream2::new()
+    }
+}
+
+// servant开始计时，__servant_request_bytes是收到的请求的字节数；没有metrics feature时不使用它
+pub(crate) fn render_servant_start() -> TokenStream2 {
+    if cfg!(feature = "metrics") {
+        render_start(&quote! { __servant_request_bytes })
+    } else {
+        quote! {
+            let _ = __servant_request_bytes;
+        }
+    }
+}
+
+// 结束计时，side是Servant或者Proxy，ok和response_bytes是调用的结果
+pub(crate) fn render_record(
+    side: &str,
+    trait_ident: &Ident,
+    fn_ident: &Ident,
+    ok: &TokenStream2,
+    response_bytes: &TokenStream2,
+) -> TokenStream2 {
+    if !cfg!(feature = "metrics") {
+        return TokenStream2::new();
+    }
+    let side = format_ident!("{}", side);
+    quote! {
+        servant::metrics::record(&servant::metrics::Call {
+            side: servant::metrics::Side::#side,
+            category: stringify!(#trait_ident),
+            method: stringify!(#fn_ident),
+            ok: #ok,
+            request_bytes: __servant_metrics_request_bytes,
+            response_bytes: #response_bytes,
+            elapsed: __servant_metrics_start.elapsed(),
+        });
+    }
+}
+
+// proxy的方法，call是调用服务端的代码，值是ServantResult，其中可能有return
+pub(crate) fn render_proxy(
+    trait_ident: &Ident,
+    fn_ident: &Ident,
+    request_bytes: &TokenStream2,
+    call: &TokenStream2,
+) -> TokenStream2 {
+    if !cfg!(feature = "metrics") {
+        return call.clone();
+    }
+    let start = render_start(request_bytes);
+    let record = render_record(
+        "Proxy",
+        trait_ident,
+        fn_ident,
+        &quote! { __servant_reps.is_ok() },
+        &quote! { bincode::serialized_size(&__servant_reps).unwrap_or_default() },
+    );
+    quote! {
+        #start
+        let __servant_reps = async { #call }.await;
+        #record
+        __servant_reps
+    }
+}
@@ -1,6 +1,8 @@
 // -- parse.rs --
 
-use crate::utilities::snake_to_camel;
+use crate::compress::{LZ4_STR, ZSTD_STR};
+use crate::utilities::{camel_to_snake, parse_rate_limit, snake_to_camel, type_to_string};
+use crate::{compress, gateway, idl, metrics, protobuf, python, trace, typescript};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use syn::{
@@ -8,8 +10,8 @@ use syn::{
     punctuated::Punctuated,
     spanned::Spanned,
     token::Comma,
-    Error, FnArg, Ident, ItemTrait, Lit, MetaNameValue, ReturnType, Signature, TraitItem,
-    TraitItemMethod,
+    Attribute, Error, FnArg, Ident, ItemStruct, ItemTrait, Lit, Meta, MetaNameValue, NestedMeta,
+    Pat, Path, ReturnType, Signature, TraitItem, TraitItemMethod, Type, TypeParamBound,
 };
 
 // --
@@ -20,9 +22,56 @@ const PERSISTENCY_STR: &str = "persistency";
 const CALLBACK_STR: &str = "callback";
 const RECEIVER_STR: &str = "receiver";
 const NOTIFIER_STR: &str = "notifier";
+const CATCH_UNWIND_STR: &str = "catch_unwind";
+const STREAM_STR: &str = "stream";
+const DELIVERY_STR: &str = "delivery";
+const OUTBOX_CAPACITY_STR: &str = "outbox_capacity";
+const RETRY_STR: &str = "retry";
+const RETRY_BACKOFF_MS_STR: &str = "retry_backoff_ms";
+const IDEMPOTENT_STR: &str = "idempotent";
+const CACHE_TTL_MS_STR: &str = "cache_ttl_ms";
+const DOC_STR: &str = "doc";
+const GRPC_STR: &str = "grpc";
+const REDACT_STR: &str = "redact";
+const RATE_LIMIT_STR: &str = "rate_limit";
+const MAX_FRAME_SIZE_STR: &str = "max_frame_size";
+const VALIDATE_STR: &str = "validate";
+const COMPRESS_STR: &str = "compress";
+const COMPRESS_THRESHOLD_STR: &str = "compress_threshold";
+const EXTENDS_STR: &str = "extends";
+const IDL_NAME_STR: &str = "idl_name";
+
+const AT_MOST_ONCE_STR: &str = "at_most_once";
+const AT_LEAST_ONCE_STR: &str = "at_least_once";
+const DEFAULT_OUTBOX_CAPACITY: usize = 1024;
+// officer最多记录这么多staff的序号，超过时淘汰最久没有报告的staff
+const DEDUP_ORIGINS: usize = 1024;
+// proxy最多缓存这么多结果，超过时淘汰最久没有使用的结果
+const CACHE_CAPACITY: usize = 256;
+const DEFAULT_RETRY: usize = 3;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 100;
+const DEFAULT_COMPRESS_THRESHOLD: usize = 4096;
+
+// 生成的proxy和batch中已经有这些方法，invoke接口的方法不能使用这些名字
+const INVOKE_RESERVED: &[&str] = &[
+    "new",
+    "category",
+    "context_mut",
+    "terminal",
+    "batch",
+    "call",
+    "invalidate_cache",
+];
+// 生成的watch proxy中已经有这些方法，watch接口的方法不能使用这些名字
+const WATCH_RESERVED: &[&str] = &["new", "category", "invalidate_cache"];
+// 生成的notifier和subscription中已经有这些方法，notify接口的方法不能使用这些名字
+const NOTIFY_RESERVED: &[&str] = &["new", "to", "to_group", "all", "subscribe"];
 
 const VALUE_EXPECT_STR: &str = "value expected '&\'static str' only.";
 const VALUE_EXPECT_BOOL: &str = "value expected 'bool' only.";
+const VALUE_EXPECT_USIZE: &str = "value expected 'usize' only.";
+const VALUE_EXPECT_U64: &str = "value expected 'u64' only.";
+const VALUE_EXPECT_RATE: &str = "value expected like '100/s', unit is 's', 'm' or 'h'.";
 
 // --
 
@@ -32,6 +81,15 @@ pub(crate) struct InvokeInterfaceAttributes {
     servant: Option<String>,
     persistency: Option<bool>,
     callback: Option<bool>,
+    catch_unwind: Option<bool>,
+    retry: Option<usize>,
+    retry_backoff_ms: Option<u64>,
+    grpc: Option<bool>,
+    max_frame_size: Option<usize>,
+    compress: Option<String>,
+    compress_threshold: Option<usize>,
+    extends: Vec<Path>,
+    idl_name: Option<String>,
 }
 impl Parse for InvokeInterfaceAttributes {
     fn parse(input: ParseStream) -> Result<Self> {
@@ -40,6 +98,15 @@ impl Parse for InvokeInterfaceAttributes {
             servant: None,
             persistency: None,
             callback: None,
+            catch_unwind: None,
+            retry: None,
+            retry_backoff_ms: None,
+            grpc: None,
+            max_frame_size: None,
+            compress: None,
+            compress_threshold: None,
+            extends: Vec::new(),
+            idl_name: None,
         };
 
         let args = Punctuated::<MetaNameValue, Comma>::parse_terminated(input)?;
@@ -52,6 +119,8 @@ impl Parse for InvokeInterfaceAttributes {
         {
             let err_str = Error::new(lit.span(), VALUE_EXPECT_STR);
             let err_bool = Error::new(lit.span(), VALUE_EXPECT_BOOL);
+            let err_usize = Error::new(lit.span(), VALUE_EXPECT_USIZE);
+            let err_u64 = Error::new(lit.span(), VALUE_EXPECT_U64);
 
             if path.is_ident(PROXY_STR) {
                 if let Lit::Str(lit_str) = lit {
@@ -81,12 +150,83 @@ impl Parse for InvokeInterfaceAttributes {
                 } else {
                     Err(err_bool)?;
                 }
+            } else if path.is_ident(CATCH_UNWIND_STR) {
+                if let Lit::Bool(lit_bool) = lit {
+                    let v = lit_bool.value;
+                    r.catch_unwind.replace(v);
+                } else {
+                    Err(err_bool)?;
+                }
+            } else if path.is_ident(RETRY_STR) {
+                if let Lit::Int(lit_int) = lit {
+                    let v = lit_int.base10_parse::<usize>()?;
+                    r.retry.replace(v);
+                } else {
+                    Err(err_usize)?;
+                }
+            } else if path.is_ident(RETRY_BACKOFF_MS_STR) {
+                if let Lit::Int(lit_int) = lit {
+                    let v = lit_int.base10_parse::<u64>()?;
+                    r.retry_backoff_ms.replace(v);
+                } else {
+                    Err(err_u64)?;
+                }
+            } else if path.is_ident(GRPC_STR) {
+                if let Lit::Bool(lit_bool) = lit {
+                    let v = lit_bool.value;
+                    r.grpc.replace(v);
+                } else {
+                    Err(err_bool)?;
+                }
+            } else if path.is_ident(MAX_FRAME_SIZE_STR) {
+                if let Lit::Int(lit_int) = lit {
+                    let v = lit_int.base10_parse::<usize>()?;
+                    r.max_frame_size.replace(v);
+                } else {
+                    Err(err_usize)?;
+                }
+            } else if path.is_ident(COMPRESS_STR) {
+                r.compress.replace(parse_compress(lit)?);
+            } else if path.is_ident(COMPRESS_THRESHOLD_STR) {
+                if let Lit::Int(lit_int) = lit {
+                    let v = lit_int.base10_parse::<usize>()?;
+                    r.compress_threshold.replace(v);
+                } else {
+                    Err(err_usize)?;
+                }
+            } else if path.is_ident(EXTENDS_STR) {
+                // 多个父接口使用逗号分隔，例如extends = "Store, super::Admin"
+                if let Lit::Str(lit_str) = lit {
+                    let v = lit_str.parse_with(Punctuated::<Path, Comma>::parse_terminated)?;
+                    r.extends.extend(v);
+                } else {
+                    Err(err_str)?;
+                }
+            } else if path.is_ident(IDL_NAME_STR) {
+                if let Lit::Str(lit_str) = lit {
+                    let v = lit_str.value();
+                    r.idl_name.replace(v);
+                } else {
+                    Err(err_str)?;
+                }
             } else {
                 Err(Error::new(
                     path.span(),
                     format!(
-                        "name expected '{}', '{}', '{}' or '{}' only.",
-                        PROXY_STR, SERVANT_STR, PERSISTENCY_STR, CALLBACK_STR
+                        "name expected '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}' or '{}' only.",
+                        PROXY_STR,
+                        SERVANT_STR,
+                        PERSISTENCY_STR,
+                        CALLBACK_STR,
+                        CATCH_UNWIND_STR,
+                        RETRY_STR,
+                        RETRY_BACKOFF_MS_STR,
+                        GRPC_STR,
+                        MAX_FRAME_SIZE_STR,
+                        COMPRESS_STR,
+                        COMPRESS_THRESHOLD_STR,
+                        EXTENDS_STR,
+                        IDL_NAME_STR
                     ),
                 ))?;
             }
@@ -101,12 +241,18 @@ impl Parse for InvokeInterfaceAttributes {
 pub(crate) struct WatchInterfaceAttributes {
     proxy: Option<String>,
     servant: Option<String>,
+    catch_unwind: Option<bool>,
+    max_frame_size: Option<usize>,
+    idl_name: Option<String>,
 }
 impl Parse for WatchInterfaceAttributes {
     fn parse(input: ParseStream) -> Result<Self> {
         let mut r = Self {
             proxy: None,
             servant: None,
+            catch_unwind: None,
+            max_frame_size: None,
+            idl_name: None,
         };
 
         let args = Punctuated::<MetaNameValue, Comma>::parse_terminated(input)?;
@@ -118,6 +264,8 @@ impl Parse for WatchInterfaceAttributes {
         } in args.iter()
         {
             let err_str = Error::new(lit.span(), VALUE_EXPECT_STR);
+            let err_bool = Error::new(lit.span(), VALUE_EXPECT_BOOL);
+            let err_usize = Error::new(lit.span(), VALUE_EXPECT_USIZE);
 
             if path.is_ident(PROXY_STR) {
                 if let Lit::Str(lit_str) = lit {
@@ -133,10 +281,40 @@ impl Parse for WatchInterfaceAttributes {
                 } else {
                     Err(err_str)?;
                 }
+            } else if path.is_ident(CATCH_UNWIND_STR) {
+                if let Lit::Bool(lit_bool) = lit {
+                    let v = lit_bool.value;
+                    r.catch_unwind.replace(v);
+                } else {
+                    Err(err_bool)?;
+                }
+            } else if path.is_ident(MAX_FRAME_SIZE_STR) {
+                if let Lit::Int(lit_int) = lit {
+                    let v = lit_int.base10_parse::<usize>()?;
+                    r.max_frame_size.replace(v);
+                } else {
+                    Err(err_usize)?;
+                }
+            } else if path.is_ident(COMPRESS_STR) || path.is_ident(COMPRESS_THRESHOLD_STR) {
+                // watch的请求是方法的序号，可能与压缩的flag相同
+                Err(Error::new(
+                    path.span(),
+                    format!("'{}' is used in invoke interface only.", COMPRESS_STR),
+                ))?;
+            } else if path.is_ident(IDL_NAME_STR) {
+                if let Lit::Str(lit_str) = lit {
+                    let v = lit_str.value();
+                    r.idl_name.replace(v);
+                } else {
+                    Err(err_str)?;
+                }
             } else {
                 Err(Error::new(
                     path.span(),
-                    format!("name expected '{}' or '{}' only.", PROXY_STR, SERVANT_STR),
+                    format!(
+                        "name expected '{}', '{}', '{}', '{}' or '{}' only.",
+                        PROXY_STR, SERVANT_STR, CATCH_UNWIND_STR, MAX_FRAME_SIZE_STR, IDL_NAME_STR
+                    ),
                 ))?;
             }
         }
@@ -150,12 +328,22 @@ impl Parse for WatchInterfaceAttributes {
 pub(crate) struct ReportInterfaceAttributes {
     proxy: Option<String>,
     servant: Option<String>,
+    catch_unwind: Option<bool>,
+    delivery: Option<String>,
+    outbox_capacity: Option<usize>,
+    max_frame_size: Option<usize>,
+    idl_name: Option<String>,
 }
 impl Parse for ReportInterfaceAttributes {
     fn parse(input: ParseStream) -> Result<Self> {
         let mut r = Self {
             proxy: None,
             servant: None,
+            catch_unwind: None,
+            delivery: None,
+            outbox_capacity: None,
+            max_frame_size: None,
+            idl_name: None,
         };
 
         let args = Punctuated::<MetaNameValue, Comma>::parse_terminated(input)?;
@@ -167,6 +355,8 @@ impl Parse for ReportInterfaceAttributes {
         } in args.iter()
         {
             let err_str = Error::new(lit.span(), VALUE_EXPECT_STR);
+            let err_bool = Error::new(lit.span(), VALUE_EXPECT_BOOL);
+            let err_usize = Error::new(lit.span(), VALUE_EXPECT_USIZE);
 
             if path.is_ident(PROXY_STR) {
                 if let Lit::Str(lit_str) = lit {
@@ -182,10 +372,63 @@ impl Parse for ReportInterfaceAttributes {
                 } else {
                     Err(err_str)?;
                 }
+            } else if path.is_ident(CATCH_UNWIND_STR) {
+                if let Lit::Bool(lit_bool) = lit {
+                    let v = lit_bool.value;
+                    r.catch_unwind.replace(v);
+                } else {
+                    Err(err_bool)?;
+                }
+            } else if path.is_ident(DELIVERY_STR) {
+                if let Lit::Str(lit_str) = lit {
+                    let v = lit_str.value();
+                    if v != AT_MOST_ONCE_STR && v != AT_LEAST_ONCE_STR {
+                        Err(Error::new(
+                            lit.span(),
+                            format!(
+                                "value expected '{}' or '{}' only.",
+                                AT_MOST_ONCE_STR, AT_LEAST_ONCE_STR
+                            ),
+                        ))?;
+                    }
+                    r.delivery.replace(v);
+                } else {
+                    Err(err_str)?;
+                }
+            } else if path.is_ident(OUTBOX_CAPACITY_STR) {
+                if let Lit::Int(lit_int) = lit {
+                    let v = lit_int.base10_parse::<usize>()?;
+                    r.outbox_capacity.replace(v);
+                } else {
+                    Err(err_usize)?;
+                }
+            } else if path.is_ident(MAX_FRAME_SIZE_STR) {
+                if let Lit::Int(lit_int) = lit {
+                    let v = lit_int.base10_parse::<usize>()?;
+                    r.max_frame_size.replace(v);
+                } else {
+                    Err(err_usize)?;
+                }
+            } else if path.is_ident(IDL_NAME_STR) {
+                if let Lit::Str(lit_str) = lit {
+                    let v = lit_str.value();
+                    r.idl_name.replace(v);
+                } else {
+                    Err(err_str)?;
+                }
             } else {
                 Err(Error::new(
                     path.span(),
-                    format!("name expected '{}' or '{}' only.", PROXY_STR, SERVANT_STR),
+                    format!(
+                        "name expected '{}', '{}', '{}', '{}', '{}', '{}' or '{}' only.",
+                        PROXY_STR,
+                        SERVANT_STR,
+                        CATCH_UNWIND_STR,
+                        DELIVERY_STR,
+                        OUTBOX_CAPACITY_STR,
+                        MAX_FRAME_SIZE_STR,
+                        IDL_NAME_STR
+                    ),
                 ))?;
             }
         }
@@ -199,12 +442,20 @@ impl Parse for ReportInterfaceAttributes {
 pub(crate) struct NotifyInterfaceAttributes {
     receiver: Option<String>,
     notifier: Option<String>,
+    catch_unwind: Option<bool>,
+    stream: Option<bool>,
+    max_frame_size: Option<usize>,
+    idl_name: Option<String>,
 }
 impl Parse for NotifyInterfaceAttributes {
     fn parse(input: ParseStream) -> Result<Self> {
         let mut r = Self {
             receiver: None,
             notifier: None,
+            catch_unwind: None,
+            stream: None,
+            max_frame_size: None,
+            idl_name: None,
         };
 
         let args = Punctuated::<MetaNameValue, Comma>::parse_terminated(input)?;
@@ -216,6 +467,8 @@ impl Parse for NotifyInterfaceAttributes {
         } in args.iter()
         {
             let err_str = Error::new(lit.span(), "value expected '&\'static str' only.");
+            let err_bool = Error::new(lit.span(), VALUE_EXPECT_BOOL);
+            let err_usize = Error::new(lit.span(), VALUE_EXPECT_USIZE);
 
             if path.is_ident(NOTIFIER_STR) {
                 if let Lit::Str(lit_str) = lit {
@@ -231,12 +484,45 @@ impl Parse for NotifyInterfaceAttributes {
                 } else {
                     Err(err_str)?;
                 }
+            } else if path.is_ident(CATCH_UNWIND_STR) {
+                if let Lit::Bool(lit_bool) = lit {
+                    let v = lit_bool.value;
+                    r.catch_unwind.replace(v);
+                } else {
+                    Err(err_bool)?;
+                }
+            } else if path.is_ident(STREAM_STR) {
+                if let Lit::Bool(lit_bool) = lit {
+                    let v = lit_bool.value;
+                    r.stream.replace(v);
+                } else {
+                    Err(err_bool)?;
+                }
+            } else if path.is_ident(MAX_FRAME_SIZE_STR) {
+                if let Lit::Int(lit_int) = lit {
+                    let v = lit_int.base10_parse::<usize>()?;
+                    r.max_frame_size.replace(v);
+                } else {
+                    Err(err_usize)?;
+                }
+            } else if path.is_ident(IDL_NAME_STR) {
+                if let Lit::Str(lit_str) = lit {
+                    let v = lit_str.value();
+                    r.idl_name.replace(v);
+                } else {
+                    Err(err_str)?;
+                }
             } else {
                 Err(Error::new(
                     path.span(),
                     format!(
-                        "name expected '{}' or '{}' only.",
-                        NOTIFIER_STR, RECEIVER_STR
+                        "name expected '{}', '{}', '{}', '{}', '{}' or '{}' only.",
+                        NOTIFIER_STR,
+                        RECEIVER_STR,
+                        CATCH_UNWIND_STR,
+                        STREAM_STR,
+                        MAX_FRAME_SIZE_STR,
+                        IDL_NAME_STR
                     ),
                 ))?;
             }
@@ -247,6 +533,129 @@ impl Parse for NotifyInterfaceAttributes {
 
 // --
 
+#[derive(Default)]
+#[cfg_attr(test, derive(Debug))]
+pub(crate) struct MethodAttributes {
+    idempotent: bool,
+    cache_ttl_ms: Option<u64>,
+    // (次数, 毫秒)，每个客户端在这个时间内最多调用的次数
+    rate_limit: Option<(u64, u64)>,
+    // 在调用实现类的方法之前，以解码后的参数调用这个函数，返回Err时不调用方法
+    validate: Option<Path>,
+    // 方法使用的压缩算法，没有时使用接口的compress
+    compress: Option<String>,
+    // 每个参数是否使用了#[servant(redact)]，tracing中不记录这些参数的值
+    pub(crate) redact_vec: Vec<bool>,
+}
+impl MethodAttributes {
+    fn parse(method: &TraitItemMethod) -> Result<Self> {
+        let mut r = Self::default();
+
+        for attr in method.attrs.iter().filter(|a| a.path.is_ident(SERVANT_STR)) {
+            let nested = if let Meta::List(list) = attr.parse_meta()? {
+                list.nested
+            } else {
+                Err(Error::new(attr.span(), "expected '#[servant(...)]'."))?
+            };
+            for meta in nested.iter() {
+                match meta {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident(IDEMPOTENT_STR) => {
+                        r.idempotent = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. }))
+                        if path.is_ident(CACHE_TTL_MS_STR) =>
+                    {
+                        if let Lit::Int(lit_int) = lit {
+                            let v = lit_int.base10_parse::<u64>()?;
+                            r.cache_ttl_ms.replace(v);
+                        } else {
+                            Err(Error::new(lit.span(), VALUE_EXPECT_U64))?;
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. }))
+                        if path.is_ident(RATE_LIMIT_STR) =>
+                    {
+                        if let Lit::Str(lit_str) = lit {
+                            let v = parse_rate_limit(&lit_str.value())
+                                .ok_or_else(|| Error::new(lit.span(), VALUE_EXPECT_RATE))?;
+                            r.rate_limit.replace(v);
+                        } else {
+                            Err(Error::new(lit.span(), VALUE_EXPECT_RATE))?;
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. }))
+                        if path.is_ident(VALIDATE_STR) =>
+                    {
+                        if let Lit::Str(lit_str) = lit {
+                            let v = lit_str.parse::<Path>()?;
+                            r.validate.replace(v);
+                        } else {
+                            Err(Error::new(lit.span(), VALUE_EXPECT_STR))?;
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. }))
+                        if path.is_ident(COMPRESS_STR) =>
+                    {
+                        r.compress.replace(parse_compress(lit)?);
+                    }
+                    _ => Err(Error::new(
+                        meta.span(),
+                        format!(
+                            "name expected '{}', '{}', '{}', '{}' or '{}' only.",
+                            IDEMPOTENT_STR,
+                            CACHE_TTL_MS_STR,
+                            RATE_LIMIT_STR,
+                            VALIDATE_STR,
+                            COMPRESS_STR
+                        ),
+                    ))?,
+                }
+            }
+        }
+        for input in method.sig.inputs.iter() {
+            if let FnArg::Typed(pat) = input {
+                let mut redact = false;
+                for attr in pat.attrs.iter().filter(|a| a.path.is_ident(SERVANT_STR)) {
+                    let nested = if let Meta::List(list) = attr.parse_meta()? {
+                        list.nested
+                    } else {
+                        Err(Error::new(attr.span(), "expected '#[servant(...)]'."))?
+                    };
+                    for meta in nested.iter() {
+                        match meta {
+                            NestedMeta::Meta(Meta::Path(path)) if path.is_ident(REDACT_STR) => {
+                                redact = true;
+                            }
+                            _ => Err(Error::new(
+                                meta.span(),
+                                format!("name expected '{}' only.", REDACT_STR),
+                            ))?,
+                        }
+                    }
+                }
+                r.redact_vec.push(redact);
+            }
+        }
+        Ok(r)
+    }
+}
+
+// compress的值，只能是zstd或者lz4
+fn parse_compress(lit: &Lit) -> Result<String> {
+    if let Lit::Str(lit_str) = lit {
+        let v = lit_str.value();
+        if v == ZSTD_STR || v == LZ4_STR {
+            return Ok(v);
+        }
+    }
+    Err(Error::new(
+        lit.span(),
+        format!("value expected '{}' or '{}' only.", ZSTD_STR, LZ4_STR),
+    ))
+}
+
+// --
+
 // #[allow(unused)]
 #[cfg_attr(test, derive(Debug))]
 pub(crate) struct TraitContext {
@@ -257,9 +666,12 @@ pub(crate) struct TraitContext {
     inputs_vec: Vec<Vec<TokenStream2>>,
     method_with_context_vec: Vec<TokenStream2>,
     method_vec: Vec<TraitItemMethod>,
+    method_attributes_vec: Vec<MethodAttributes>,
     output_vec: Vec<TokenStream2>,
+    entity_guard_vec: Vec<Ident>,
     request_ident_vec: Vec<Ident>,
     request_ident: Ident,
+    method_descriptor_vec: Vec<TokenStream2>,
 }
 
 impl Parse for TraitContext {
@@ -280,6 +692,23 @@ impl Parse for TraitContext {
             .filter(|i| i.is_some())
             .map(|x| x.unwrap().clone())
             .collect();
+        // #[servant(...)]只用于生成代码，解析之后从方法中去掉
+        let method_attributes_vec = method_vec
+            .iter()
+            .map(MethodAttributes::parse)
+            .collect::<Result<Vec<_>>>()?;
+        let method_vec: Vec<_> = method_vec
+            .into_iter()
+            .map(|mut m| {
+                m.attrs.retain(|a| !a.path.is_ident(SERVANT_STR));
+                for input in m.sig.inputs.iter_mut() {
+                    if let FnArg::Typed(pat) = input {
+                        pat.attrs.retain(|a| !a.path.is_ident(SERVANT_STR));
+                    }
+                }
+                m
+            })
+            .collect();
         let idents_collected: Vec<_> = method_vec
             .iter()
             .map(|x| {
@@ -311,6 +740,19 @@ impl Parse for TraitContext {
                 };
                 let fn_ident_camel =
                     Ident::new(&snake_to_camel(&fn_ident.to_string()), fn_ident.span());
+                // &self的方法共享读锁，&mut self的方法独占写锁
+                let receiver_mut = inputs.iter().any(|i| {
+                    if let FnArg::Receiver(receiver) = i {
+                        receiver.mutability.is_some()
+                    } else {
+                        false
+                    }
+                });
+                let entity_guard = if receiver_mut {
+                    format_ident!("write")
+                } else {
+                    format_ident!("read")
+                };
                 let args: Vec<_> = inputs
                     .iter()
                     .map(|i| {
@@ -326,6 +768,36 @@ impl Parse for TraitContext {
                         quote! {#x,}
                     })
                     .collect();
+                // 方法的描述，在运行时列出接口的方法
+                let params: Vec<_> = inputs
+                    .iter()
+                    .filter_map(|i| {
+                        if let FnArg::Typed(pat) = i {
+                            Some(pat)
+                        } else {
+                            None
+                        }
+                    })
+                    .map(|x| {
+                        let (pat, ty) = (&x.pat, &x.ty);
+                        let name = type_to_string(&quote! {#pat}.to_string());
+                        let ty = type_to_string(&quote! {#ty}.to_string());
+                        quote! { servant::ParamDescriptor { name: #name, ty: #ty }, }
+                    })
+                    .collect();
+                let method_descriptor = {
+                    let name = fn_ident.to_string();
+                    let output = type_to_string(&output_type.to_string());
+                    let doc = doc_string(attrs);
+                    quote! {
+                        servant::MethodDescriptor {
+                            name: #name,
+                            params: &[#(#params)*],
+                            output: #output,
+                            doc: #doc,
+                        },
+                    }
+                };
                 let input_receiver: Vec<_> = inputs
                     .iter()
                     .map(|i| {
@@ -371,6 +843,8 @@ impl Parse for TraitContext {
                     inputs,
                     method_with_context,
                     output_type,
+                    entity_guard,
+                    method_descriptor,
                 )
             })
             .collect();
@@ -381,13 +855,14 @@ impl Parse for TraitContext {
         let method_with_context_vec: Vec<_> =
             idents_collected.iter().map(|i| i.4.clone()).collect();
         let output_vec: Vec<_> = idents_collected.iter().map(|i| i.5.clone()).collect();
+        let entity_guard_vec: Vec<_> = idents_collected.iter().map(|i| i.6.clone()).collect();
+        let method_descriptor_vec: Vec<_> = idents_collected.iter().map(|i| i.7.clone()).collect();
 
         let request_ident = format_ident!("{}Request", trait_ident);
         let request_ident_vec: Vec<_> = idents_collected
             .iter()
             .map(|_| request_ident.clone())
             .collect();
-
         Ok(Self {
             item_trait,
             fn_ident_vec,
@@ -396,14 +871,295 @@ impl Parse for TraitContext {
             inputs_vec,
             method_with_context_vec,
             method_vec,
+            method_attributes_vec,
             output_vec,
+            entity_guard_vec,
             request_ident_vec,
             request_ident,
+            method_descriptor_vec,
         })
     }
 }
 
 impl TraitContext {
+    pub(crate) fn interface(&self, kind: &str) -> idl::Interface {
+        let methods = self
+            .method_vec
+            .iter()
+            .map(|m| {
+                let params = m
+                    .sig
+                    .inputs
+                    .iter()
+                    .filter_map(|i| {
+                        if let FnArg::Typed(pat) = i {
+                            Some(pat)
+                        } else {
+                            None
+                        }
+                    })
+                    .map(|x| {
+                        let pat = &x.pat;
+                        idl::Param {
+                            name: type_to_string(&quote! {#pat}.to_string()),
+                            ty: (*x.ty).clone(),
+                        }
+                    })
+                    .collect();
+                let output: Type = match &m.sig.output {
+                    ReturnType::Default => parse_quote! {()},
+                    ReturnType::Type(_, t) => (**t).clone(),
+                };
+                idl::Method {
+                    name: m.sig.ident.to_string(),
+                    doc: doc_string(&m.attrs),
+                    params,
+                    output,
+                }
+            })
+            .collect();
+        idl::Interface {
+            kind: kind.to_string(),
+            name: self.item_trait.ident.to_string(),
+            doc: doc_string(&self.item_trait.attrs),
+            methods,
+        }
+    }
+
+    // 检查接口的定义是否可以用于kind类型的接口
+    pub(crate) fn check(&self, kind: &str) -> Result<()> {
+        let reserved: &[&str] = match kind {
+            "invoke" => INVOKE_RESERVED,
+            "watch" => WATCH_RESERVED,
+            "notify" => NOTIFY_RESERVED,
+            _ => &[],
+        };
+        // 有缓存的方法，proxy中生成invalidate_xxx方法
+        let invalidate_vec: Vec<_> = self
+            .method_vec
+            .iter()
+            .zip(self.method_attributes_vec.iter())
+            .filter(|(_, a)| a.cache_ttl_ms.is_some())
+            .map(|(m, _)| format!("invalidate_{}", m.sig.ident))
+            .collect();
+        for (m, a) in self
+            .method_vec
+            .iter()
+            .zip(self.method_attributes_vec.iter())
+        {
+            let ident = &m.sig.ident;
+            // 参数的名字是请求中字段的名字，也用于JSON和其他语言的代码，只能是标识符
+            for input in m.sig.inputs.iter() {
+                if let FnArg::Typed(pat) = input {
+                    match &*pat.pat {
+                        Pat::Ident(p)
+                            if p.by_ref.is_none()
+                                && p.mutability.is_none()
+                                && p.subpat.is_none() => {}
+                        p => Err(Error::new(
+                            p.span(),
+                            "parameter expected an identifier, such as 'n: i32'.",
+                        ))?,
+                    }
+                }
+            }
+            let name = ident.to_string();
+            if reserved.contains(&name.as_str()) || invalidate_vec.contains(&name) {
+                Err(Error::new(
+                    ident.span(),
+                    format!("method name '{}' is reserved in {} interface.", ident, kind),
+                ))?;
+            }
+            if a.idempotent && kind != "invoke" {
+                Err(Error::new(
+                    ident.span(),
+                    format!("'{}' is used in invoke interface only.", IDEMPOTENT_STR),
+                ))?;
+            }
+            if a.compress.is_some() && kind != "invoke" {
+                Err(Error::new(
+                    ident.span(),
+                    format!("'{}' is used in invoke interface only.", COMPRESS_STR),
+                ))?;
+            }
+            if a.rate_limit.is_some() && kind != "invoke" {
+                Err(Error::new(
+                    ident.span(),
+                    format!("'{}' is used in invoke interface only.", RATE_LIMIT_STR),
+                ))?;
+            }
+            if a.cache_ttl_ms.is_some() && kind != "invoke" && kind != "watch" {
+                Err(Error::new(
+                    ident.span(),
+                    format!(
+                        "'{}' is used in invoke and watch interface only.",
+                        CACHE_TTL_MS_STR
+                    ),
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    // extends中的父接口必须也是接口的supertrait，实现类才能实现父接口的方法
+    pub(crate) fn check_extends(&self, attributes: &InvokeInterfaceAttributes) -> Result<()> {
+        let supertrait_vec: Vec<_> = self
+            .item_trait
+            .supertraits
+            .iter()
+            .filter_map(|b| match b {
+                TypeParamBound::Trait(t) => t.path.segments.last().map(|s| &s.ident),
+                _ => None,
+            })
+            .collect();
+        for p in attributes.extends.iter() {
+            let last = &p.segments.last().unwrap().ident;
+            if !supertrait_vec.contains(&last) {
+                Err(Error::new(
+                    last.span(),
+                    format!(
+                        "'{}' in '{}' must be a supertrait of the interface.",
+                        last, EXTENDS_STR
+                    ),
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    // 打开idl、typescript、python等feature时，将接口的定义以及其他语言的代码导出到OUT_DIR中；
+    // 文件名缺省是接口的名字，不同模块中同名的接口使用idl_name区分，否则会覆盖同一个文件
+    fn render_export(&self, kind: &str, idl_name: Option<&str>) -> TokenStream2 {
+        let interface = self.interface(kind);
+        let file_stem = idl_name.unwrap_or(&interface.name);
+        let mut exports = Vec::new();
+        if cfg!(feature = "idl") {
+            exports.push((format!("{}.json", file_stem), interface.to_json()));
+        }
+        if cfg!(feature = "idl") && kind == "invoke" {
+            exports.push((
+                format!("{}.proto", file_stem),
+                protobuf::render_proto(&interface),
+            ));
+        }
+        if cfg!(feature = "typescript") && typescript::supported(kind) {
+            exports.push((
+                typescript::RUNTIME_FILE.to_string(),
+                typescript::RUNTIME.to_string(),
+            ));
+            exports.push((format!("{}.ts", file_stem), typescript::render(&interface)));
+        }
+        if cfg!(feature = "python") && python::supported(kind) {
+            exports.push((
+                python::RUNTIME_FILE.to_string(),
+                python::RUNTIME.to_string(),
+            ));
+            exports.push((format!("{}.py", file_stem), python::render(&interface)));
+        }
+        match exports
+            .iter()
+            .try_for_each(|(file_name, content)| idl::export(file_name, content).map(|_| ()))
+        {
+            Ok(_) => TokenStream2::new(),
+            Err(e) => idl::render_export_error(e),
+        }
+    }
+
+    // 接口的描述，生成servant和proxy的DESCRIPTOR
+    fn render_descriptor(&self, kind: &str) -> TokenStream2 {
+        let kind = format_ident!("{}", kind);
+        let name = self.item_trait.ident.to_string();
+        let doc = doc_string(&self.item_trait.attrs);
+        let method_descriptor_vec = &self.method_descriptor_vec;
+        quote! {
+            servant::InterfaceDescriptor {
+                kind: servant::InterfaceKind::#kind,
+                name: #name,
+                doc: #doc,
+                methods: &[#(#method_descriptor_vec)*],
+            }
+        }
+    }
+
+    // 服务端每个方法调用的metrics，response为false时没有响应
+    fn render_metrics_record_vec(&self, response: bool) -> Vec<TokenStream2> {
+        let response_bytes = if response {
+            quote! { bincode::serialized_size(&__servant_reps).unwrap_or_default() }
+        } else {
+            quote! { 0 }
+        };
+        self.fn_ident_vec
+            .iter()
+            .map(|fn_ident| {
+                metrics::render_record(
+                    "Servant",
+                    &self.item_trait.ident,
+                    fn_ident,
+                    &quote! { __servant_reps.is_ok() },
+                    &response_bytes,
+                )
+            })
+            .collect()
+    }
+
+    // servant调用实现类的表达式，值是ServantResult；call是调用实现类方法的代码。rate_limit和validate的检查
+    // 失败时不调用实现类，错误与实现类返回的错误一样经过metrics和tracing
+    fn render_servant_call_vec<F>(&self, call: F) -> Vec<TokenStream2>
+    where
+        F: Fn(&Ident, &Ident, &[TokenStream2]) -> TokenStream2,
+    {
+        self.method_attributes_vec
+            .iter()
+            .zip(self.fn_ident_vec.iter().zip(self.entity_guard_vec.iter()))
+            .zip(self.args_vec.iter())
+            .map(|((a, (fn_ident, entity_guard)), args)| {
+                let call = call(fn_ident, entity_guard, args);
+                let trait_ident = &self.item_trait.ident;
+                match render_precheck(
+                    trait_ident,
+                    fn_ident,
+                    a.rate_limit,
+                    a.validate.as_ref(),
+                    args,
+                ) {
+                    Some(precheck) => {
+                        quote! { #precheck.and_then(|_| Self::unwind_guard(|| #call)) }
+                    }
+                    None => quote! { Self::unwind_guard(|| #call) },
+                }
+            })
+            .collect()
+    }
+
+    // 每个方法使用的压缩算法和对应的Option<(flag, threshold)>，方法没有compress时使用接口的compress
+    fn render_compression_vec<'a>(
+        &'a self,
+        compress: Option<&'a str>,
+        threshold: usize,
+    ) -> (Vec<Option<&'a str>>, Vec<TokenStream2>) {
+        let compress_vec: Vec<_> = self
+            .method_attributes_vec
+            .iter()
+            .map(|a| a.compress.as_deref().or(compress))
+            .collect();
+        let compression_vec = compress_vec
+            .iter()
+            .map(|c| compress::render_compression(*c, threshold))
+            .collect();
+        (compress_vec, compression_vec)
+    }
+
+    // 每个方法调用的tracing span，trace_id是记录trace id的表达式
+    fn render_span_vec(&self, trace_id: Option<TokenStream2>) -> Vec<TokenStream2> {
+        self.method_vec
+            .iter()
+            .zip(self.method_attributes_vec.iter())
+            .map(|(m, a)| {
+                trace::render_span(&self.item_trait.ident, m, &a.redact_vec, trace_id.as_ref())
+            })
+            .collect()
+    }
+
     pub(crate) fn render_invoke_interface(
         &self,
         attributes: &InvokeInterfaceAttributes,
@@ -430,11 +1186,15 @@ impl TraitContext {
             inputs_vec,
             method_with_context_vec,
             // method_vec,
+            method_attributes_vec,
             output_vec,
+            // entity_guard_vec,
             request_ident_vec,
             request_ident,
             ..
         } = self;
+        let super_interface_vec = &attributes.extends;
+        let descriptor = self.render_descriptor("Invoke");
 
         let fn_ident_callback_vec: Vec<_> = fn_ident_vec
             .iter()
@@ -451,13 +1211,327 @@ impl TraitContext {
         } else {
             format_ident!("{}Proxy", trait_ident)
         };
+        let frame_ident = format_ident!("{}Frame", trait_ident);
+        let response_ident = format_ident!("{}Response", trait_ident);
+        let batch_ident = format_ident!("{}Batch", proxy_ident);
+        // 父接口生成的XxxFrame和XxxProxy，与父接口定义在同一个模块中
+        let super_frame_vec: Vec<_> = super_interface_vec
+            .iter()
+            .map(|p| replace_last_ident(p, "Frame"))
+            .collect();
+        let super_proxy_vec: Vec<_> = super_interface_vec
+            .iter()
+            .map(|p| replace_last_ident(p, "Proxy"))
+            .collect();
+        let as_super_vec: Vec<_> = super_interface_vec
+            .iter()
+            .map(|p| {
+                let last = &p.segments.last().unwrap().ident;
+                format_ident!("as_{}", camel_to_snake(&last.to_string()))
+            })
+            .collect();
 
-        let output1 = if cfg!(any(feature = "server", feature = "client")) {
-            quote! {
-                #[derive(serde::Serialize, serde::Deserialize)]
-                enum #request_ident {
-                    #(#fn_ident_camel_vec { #(#inputs_vec)* },)*
+        let unwind_guard = render_unwind_guard(attributes.catch_unwind.unwrap_or(true));
+        let retry = attributes.retry.unwrap_or(DEFAULT_RETRY);
+        let retry_backoff_ms = attributes
+            .retry_backoff_ms
+            .unwrap_or(DEFAULT_RETRY_BACKOFF_MS);
+        // tracing feature：proxy的Context中没有trace id时生成新的，随请求发送给服务端
+        let (trace_context, context) = if cfg!(feature = "tracing") {
+            (
+                quote! {
+                    let mut __servant_trace_ctx = self.0.clone();
+                    if __servant_trace_ctx.trace_id().is_none() {
+                        __servant_trace_ctx.set_trace_id(servant::new_trace_id());
+                    }
+                },
+                quote! { __servant_trace_ctx.clone() },
+            )
+        } else {
+            (proc_macro2::TokenStream::new(), quote! { self.0.clone() })
+        };
+        let proxy_span_vec = self.render_span_vec(Some(
+            quote! { __servant_trace_ctx.trace_id().unwrap_or_default() },
+        ));
+        let servant_enter_vec: Vec<_> = self
+            .render_span_vec(Some(
+                quote! { __servant_ctx.as_ref().and_then(|c| c.trace_id()).unwrap_or_default() },
+            ))
+            .iter()
+            .map(trace::render_enter)
+            .collect();
+        let trace_event = trace::render_event(&quote! { __servant_reps });
+        let trace_event_vec: Vec<_> = fn_ident_vec.iter().map(|_| trace_event.clone()).collect();
+        // metrics feature：记录每个方法调用的次数、字节数和耗时
+        let metrics_start = metrics::render_servant_start();
+        let metrics_record_vec = self.render_metrics_record_vec(true);
+        // compress的方法，proxy压缩超过threshold的请求，servant压缩响应
+        let compress_threshold = attributes
+            .compress_threshold
+            .unwrap_or(DEFAULT_COMPRESS_THRESHOLD);
+        let (compress_vec, compression_vec) =
+            self.render_compression_vec(attributes.compress.as_deref(), compress_threshold);
+        let compressed = compress_vec.iter().any(Option::is_some);
+        let output_codec = compress::render_codec(
+            request_ident,
+            fn_ident_camel_vec,
+            &compression_vec,
+            &compress_vec,
+        );
+        // proxy在Context中设置accept_compress，收到压缩的响应之后，知道servant可以解压，才开始压缩请求。
+        // 这个状态是proxy最后的field，在缓存和父接口的proxy之后
+        let accepted_index = syn::Index::from(
+            3 + method_attributes_vec
+                .iter()
+                .any(|a| a.cache_ttl_ms.is_some()) as usize
+                + super_proxy_vec.len().min(1),
+        );
+        let (accepted_field, accepted_init, accept_context) = if compressed {
+            (
+                quote! { , std::sync::Arc<std::sync::atomic::AtomicBool> },
+                quote! { , std::sync::Arc::default() },
+                quote! {
+                    let mut ctx = ctx;
+                    ctx.set_accept_compress(true);
+                },
+            )
+        } else {
+            (
+                proc_macro2::TokenStream::new(),
+                proc_macro2::TokenStream::new(),
+                proc_macro2::TokenStream::new(),
+            )
+        };
+        let decompress_response = if compressed {
+            compress::render_decompress_response(request_ident, &quote! { self.#accepted_index })
+        } else {
+            quote! { response }
+        };
+        let compress_request_vec: Vec<_> = compress_vec
+            .iter()
+            .zip(compression_vec.iter())
+            .map(|(c, compression)| {
+                if c.is_some() {
+                    quote! {
+                        let req = if self.#accepted_index.load(std::sync::atomic::Ordering::Relaxed) {
+                            #request_ident::compress(#compression, req)
+                        } else {
+                            req
+                        };
+                    }
+                } else {
+                    proc_macro2::TokenStream::new()
                 }
+            })
+            .collect();
+        // 批量调用中包含不同的方法，使用接口的compress，接口没有时使用第一个compress的方法
+        let (compress_batch, accept_batch) = if compressed {
+            let compression = compress::render_compression(
+                attributes
+                    .compress
+                    .as_deref()
+                    .or_else(|| compress_vec.iter().flatten().next().copied()),
+                compress_threshold,
+            );
+            (
+                quote! {
+                    let req = if proxy.#accepted_index.load(std::sync::atomic::Ordering::Relaxed) {
+                        #request_ident::compress(#compression, req)
+                    } else {
+                        req
+                    };
+                },
+                quote! {
+                    if reps.iter().any(|v| #request_ident::is_compressed(v)) {
+                        proxy.#accepted_index.store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                },
+            )
+        } else {
+            (
+                proc_macro2::TokenStream::new(),
+                proc_macro2::TokenStream::new(),
+            )
+        };
+        // 不能解码的响应作为ServantError返回，不会panic
+        let batch_decode_vec: Vec<_> = output_vec
+            .iter()
+            .map(|output| {
+                let decode = quote! {
+                    bincode::deserialize::<servant::ServantResult<#output>>(&v)
+                        .unwrap_or_else(|e| Err(format!("invalid response: {}", e).into()))
+                };
+                if compressed {
+                    quote! {
+                        #request_ident::decompress(v.to_vec(), None)
+                            .map_err(servant::ServantError::from)
+                            .and_then(|(_, v)| #decode)
+                    }
+                } else {
+                    decode
+                }
+            })
+            .collect();
+        // proxy可以接收压缩的响应时，servant按照方法的compress压缩每个调用的响应
+        let reply = |dispatch: TokenStream2| {
+            if compressed {
+                quote! {
+                    {
+                        let compression = if accept { __servant_req.compression() } else { None };
+                        #request_ident::compress(compression, #dispatch)
+                    }
+                }
+            } else {
+                dispatch
+            }
+        };
+        let reply_call = reply(quote! {
+            Self::dispatch(__servant_entity, __servant_limiter, __servant_ctx, __servant_req, __servant_request_bytes)
+        });
+        let reply_batch = reply(quote! {
+            Self::dispatch(__servant_entity, __servant_limiter, __servant_ctx.clone(), __servant_req, __servant_request_bytes)
+        });
+        // idempotent的方法，在传输错误时按照指数退避重试；方法返回的ServantError和其他方法都不重试
+        let invoke_vec: Vec<_> = method_attributes_vec
+            .iter()
+            .map(|a| {
+                let invoke = if a.idempotent {
+                    quote! {
+                        let mut retry = 0;
+                        let mut backoff: u64 = #retry_backoff_ms;
+                        loop {
+                            let response = self.2.invoke(Some(ctx.clone()), Some(self.1.clone()), req.clone()).await;
+                            match &response {
+                                Err(e) if e.is_transport() && retry < #retry => {}
+                                _ => break response,
+                            }
+                            retry += 1;
+                            servant::sleep(std::time::Duration::from_millis(backoff)).await;
+                            backoff = backoff.saturating_mul(2);
+                        }
+                    }
+                } else {
+                    quote! {
+                        self.2.invoke(Some(ctx), Some(self.1.clone()), req).await
+                    }
+                };
+                // 请求完成之前future被drop时，guard通知服务端取消这个请求
+                quote! {
+                    let (ctx, guard) = servant::CancelGuard::new(#context, &self.2);
+                    #accept_context
+                    let response = { #invoke };
+                    guard.disarm();
+                    #decompress_response
+                }
+            })
+            .collect();
+        let cache = quote! { self.3 };
+        let call_vec: Vec<_> = method_attributes_vec
+            .iter()
+            .zip(fn_ident_vec.iter().zip(output_vec.iter()))
+            .zip(invoke_vec.iter())
+            .map(|((a, (fn_ident, output)), invoke)| {
+                let call = render_cached_call(a.cache_ttl_ms, fn_ident, output, &cache, invoke);
+                render_throttled_call(a.rate_limit, retry, call)
+            })
+            .collect();
+        let (cache_field, cache_init, cache_invalidation, cache_reset) =
+            render_cache_invalidation(method_attributes_vec, fn_ident_vec, &cache);
+        // 第一个父接口的proxy保存在proxy中，通过Deref调用父接口的方法，调用时使用proxy的Context
+        let (parent_field, parent_new, parent_init, parent_reset, output_deref) =
+            if let Some(parent_proxy) = super_proxy_vec.first() {
+                let index = syn::Index::from(if cache_field.is_empty() { 3 } else { 4 });
+                (
+                    quote! { , #parent_proxy },
+                    quote! { let parent = #parent_proxy::new(ctx.clone(), name, t); },
+                    quote! { , parent },
+                    quote! { self.#index.context_mut(); },
+                    quote! {
+                        impl std::ops::Deref for #proxy_ident {
+                            type Target = #parent_proxy;
+                            fn deref(&self) -> &Self::Target {
+                                &self.#index
+                            }
+                        }
+                        impl std::ops::DerefMut for #proxy_ident {
+                            fn deref_mut(&mut self) -> &mut Self::Target {
+                                self.#index.0 = self.0.clone();
+                                &mut self.#index
+                            }
+                        }
+                    },
+                )
+            } else {
+                (
+                    proc_macro2::TokenStream::new(),
+                    proc_macro2::TokenStream::new(),
+                    proc_macro2::TokenStream::new(),
+                    proc_macro2::TokenStream::new(),
+                    proc_macro2::TokenStream::new(),
+                )
+            };
+        let servant_call_vec = self.render_servant_call_vec(|fn_ident, entity_guard, args| {
+            quote! {
+                __servant_entity
+                    .#entity_guard()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .#fn_ident(__servant_ctx, #(#args)*)
+            }
+        });
+        // 请求不合法时，servant返回ServantError，不调用实现类
+        let reply_error = quote! {
+            let reps: servant::ServantResult<()> = Err(e);
+            return bincode::serialize(&reps).unwrap();
+        };
+        let decode_frame = render_decode(
+            attributes.max_frame_size,
+            &quote! { frame },
+            &quote! { Self },
+            &reply_error,
+        );
+        let decompress_request = if compressed {
+            compress::render_decompress(request_ident, attributes.max_frame_size, &reply_error)
+        } else {
+            proc_macro2::TokenStream::new()
+        };
+        let frame_check = render_frame_check(attributes.max_frame_size);
+        let proxy_body_vec: Vec<_> = fn_ident_vec
+            .iter()
+            .zip(fn_ident_camel_vec.iter().zip(args_vec.iter()))
+            .zip(call_vec.iter().zip(proxy_span_vec.iter()))
+            .zip(compress_request_vec.iter())
+            .map(
+                |(((fn_ident, (fn_ident_camel, args)), (call, span)), compress_request)| {
+                    let call = metrics::render_proxy(
+                        trait_ident,
+                        fn_ident,
+                        &quote! { req.len() as u64 },
+                        call,
+                    );
+                    let body = quote! {
+                        let request = #request_ident::#fn_ident_camel { #(#args)* };
+                        let req = bincode::serialize(&#frame_ident::Call(request)).unwrap();
+                        #frame_check
+                        #compress_request
+                        #call
+                    };
+                    trace::render_instrument(span, &body)
+                },
+            )
+            .collect();
+
+        let output1 = if cfg!(any(feature = "server", feature = "client")) {
+            quote! {
+                #[derive(serde::Serialize, serde::Deserialize)]
+                enum #request_ident {
+                    #(#fn_ident_camel_vec { #(#inputs_vec)* },)*
+                }
+                #[derive(serde::Serialize, serde::Deserialize)]
+                enum #frame_ident {
+                    Call(#request_ident),
+                    Batch(Vec<#request_ident>),
+                }
+                #output_codec
             }
         } else {
             proc_macro2::TokenStream::new()
@@ -473,18 +1547,10 @@ impl TraitContext {
                         &self.name
                     }
                     fn dump(&self) -> servant::ServantResult<Vec<u8>> {
-                        bincode::serialize(&self.entity).map_err(|e| e.to_string().into())
+                        bincode::serialize(&*self.entity.read().unwrap_or_else(std::sync::PoisonError::into_inner)).map_err(|e| e.to_string().into())
                     }
-                    fn serve(&mut self, ctx: Option<servant::Context>, req: Vec<u8>) -> Vec<u8> {
-                        let req: #request_ident = bincode::deserialize(&req).unwrap();
-                        let reps = match req {
-                            #(
-                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } =>
-                                    bincode::serialize(&self.entity.#fn_ident_vec(ctx, #(#args_vec)*)),
-                            )*
-                        }
-                        .unwrap();
-                        reps
+                    fn serve(&self, ctx: Option<servant::Context>, req: Vec<u8>) -> Vec<u8> {
+                        #frame_ident::serve(&self.entity, &self.limiter, ctx, req)
                     }
                 }
             }
@@ -497,16 +1563,33 @@ impl TraitContext {
                     fn name(&self) -> &str {
                         &self.name
                     }
-                    fn serve(&mut self, ctx: Option<servant::Context>, req: Vec<u8>) -> Vec<u8> {
-                        let req: #request_ident = bincode::deserialize(&req).unwrap();
-                        let reps = match req {
-                            #(
-                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } =>
-                                    bincode::serialize(&self.entity.#fn_ident_vec(ctx, #(#args_vec)*)),
-                            )*
-                        }
-                        .unwrap();
-                        reps
+                    fn serve(&self, ctx: Option<servant::Context>, req: Vec<u8>) -> Vec<u8> {
+                        #frame_ident::serve(&self.entity, &self.limiter, ctx, req)
+                    }
+                }
+            }
+        };
+        // 有父接口时，servant同时分发父接口的请求
+        let output_inheritance = if super_interface_vec.is_empty() {
+            proc_macro2::TokenStream::new()
+        } else {
+            quote! {
+                impl<S> servant::CompositeServant for #servant_ident<S>
+                where
+                    S: #trait_ident + 'static,
+                {
+                    fn name(&self) -> &str {
+                        &self.name
+                    }
+                    fn categories(&self) -> Vec<&'static str> {
+                        #frame_ident::categories()
+                    }
+                    fn serve(&self, category: &str, ctx: Option<servant::Context>, req: Vec<u8>) -> Vec<u8> {
+                        #frame_ident::serve_category(&self.entity, &self.limiter, category, ctx, req).unwrap_or_else(|| {
+                            let reps: servant::ServantResult<()> =
+                                Err(format!("category '{}' is not served.", category).into());
+                            bincode::serialize(&reps).unwrap()
+                        })
                     }
                 }
             }
@@ -520,17 +1603,116 @@ impl TraitContext {
                 pub struct #servant_ident<S>
                 {
                     name: String,
-                    entity: S,
+                    entity: std::sync::RwLock<S>,
+                    // rate_limit的计数，每个servant对象有自己的计数
+                    limiter: servant::RateLimiter,
                 }
                 impl<S> #servant_ident<S> {
                     pub fn new(name: &str, entity: S) -> Self {
-                        Self { name: name.to_string(), entity }
+                        Self {
+                            name: name.to_string(),
+                            entity: std::sync::RwLock::new(entity),
+                            limiter: servant::RateLimiter::default(),
+                        }
                     }
+                    pub const DESCRIPTOR: servant::InterfaceDescriptor = #descriptor;
                     pub fn category() -> &'static str {
                         stringify!(#trait_ident)
                     }
                 }
+                // 分发请求的代码不依赖于servant，compose生成的servant也使用这些方法
+                impl #frame_ident {
+                    fn unwind_guard<T>(f: impl FnOnce() -> T) -> servant::ServantResult<T> {
+                        #unwind_guard
+                    }
+                    // 参数和局部变量使用__servant_前缀，不会被同名的方法参数遮住；
+                    // __servant_request_bytes是收到的请求的字节数，用于metrics
+                    fn dispatch<S>(
+                        __servant_entity: &std::sync::RwLock<S>,
+                        __servant_limiter: &servant::RateLimiter,
+                        __servant_ctx: Option<servant::Context>,
+                        __servant_req: #request_ident,
+                        __servant_request_bytes: u64,
+                    ) -> Vec<u8>
+                    where
+                        S: #trait_ident,
+                    {
+                        // 只在调用实现类之前检查一次；Err的编码与T无关，proxy按照方法的返回类型解码为Err
+                        if __servant_ctx.as_ref().map_or(false, |c| c.is_cancelled()) {
+                            let reps: servant::ServantResult<()> = Err("request cancelled".to_string().into());
+                            return bincode::serialize(&reps).unwrap();
+                        }
+                        #metrics_start
+                        let reps = match __servant_req {
+                            #(
+                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } => {
+                                    #servant_enter_vec
+                                    let __servant_reps = #servant_call_vec;
+                                    #metrics_record_vec
+                                    #trace_event_vec
+                                    bincode::serialize(&__servant_reps)
+                                }
+                            )*
+                        }
+                        .unwrap();
+                        reps
+                    }
+                    fn serve<S>(
+                        __servant_entity: &std::sync::RwLock<S>,
+                        __servant_limiter: &servant::RateLimiter,
+                        __servant_ctx: Option<servant::Context>,
+                        req: Vec<u8>,
+                    ) -> Vec<u8>
+                    where
+                        S: #trait_ident,
+                    {
+                        let __servant_request_bytes = req.len() as u64;
+                        #decompress_request
+                        #decode_frame
+                        match frame {
+                            Self::Call(__servant_req) => #reply_call,
+                            Self::Batch(reqs) => {
+                                // batch中的每个请求记录平均的字节数
+                                let __servant_request_bytes = __servant_request_bytes / reqs.len().max(1) as u64;
+                                let reps: Vec<_> = reqs
+                                    .into_iter()
+                                    .map(|__servant_req| #reply_batch)
+                                    .collect();
+                                bincode::serialize(&reps).unwrap()
+                            }
+                        }
+                    }
+                    // 接口自己和所有父接口的category，用于继承和compose
+                    #[allow(dead_code)]
+                    fn categories() -> Vec<&'static str> {
+                        let mut categories = vec![stringify!(#trait_ident)];
+                        #( categories.extend(#super_frame_vec::categories()); )*
+                        categories
+                    }
+                    #[allow(dead_code)]
+                    fn serve_category<S>(
+                        __servant_entity: &std::sync::RwLock<S>,
+                        __servant_limiter: &servant::RateLimiter,
+                        category: &str,
+                        __servant_ctx: Option<servant::Context>,
+                        req: Vec<u8>,
+                    ) -> Option<Vec<u8>>
+                    where
+                        S: #trait_ident,
+                    {
+                        if category == stringify!(#trait_ident) {
+                            return Some(Self::serve(__servant_entity, __servant_limiter, __servant_ctx, req));
+                        }
+                        #(
+                            if #super_frame_vec::categories().contains(&category) {
+                                return #super_frame_vec::serve_category(__servant_entity, __servant_limiter, category, __servant_ctx, req);
+                            }
+                        )*
+                        None
+                    }
+                }
                 #output_persistence
+                #output_inheritance
             }
         } else {
             proc_macro2::TokenStream::new()
@@ -547,10 +1729,13 @@ impl TraitContext {
                     where F: 'static + Fn(servant::ServantResult<#output_vec>) + Send,
                     {
                         let request = #request_ident_vec::#fn_ident_camel_vec { #(#args_vec)* };
+                        let frame = #frame_ident::Call(request);
                         self.2
                             .invoke_with_callback(Some(self.0.clone()), Some(self.1.clone()),
-                                bincode::serialize(&request).unwrap(), move |oid, v| {
-                                    f_f_f_f_f_20101008_f(v.map(|x| bincode::deserialize(&x).unwrap()));
+                                bincode::serialize(&frame).unwrap(), move |oid, v| {
+                                    f_f_f_f_f_20101008_f(v.and_then(|x| {
+                                        bincode::deserialize::<servant::ServantResult<#output_vec>>(&x).unwrap()
+                                    }));
                                 })
                             .await
                     }
@@ -562,48 +1747,142 @@ impl TraitContext {
         let output3 = if cfg!(feature = "client") {
             quote! {
                 #[derive(Clone)]
-                pub struct #proxy_ident(servant::Context, servant::Oid, servant::Terminal);
+                pub struct #proxy_ident(
+                    servant::Context,
+                    servant::Oid,
+                    servant::Terminal
+                    #cache_field
+                    #parent_field
+                    #accepted_field
+                );
 
                 impl #proxy_ident {
                     pub fn new(ctx: servant::Context, name: &str, t: &servant::Terminal) -> Self {
                         let oid = servant::Oid::new(name, Self::category());
-                        Self(ctx, oid, t.clone())
+                        #parent_new
+                        Self(ctx, oid, t.clone() #cache_init #parent_init #accepted_init)
                     }
+                    pub const DESCRIPTOR: servant::InterfaceDescriptor = #descriptor;
                     pub fn category() -> &'static str {
                         stringify!(#trait_ident)
                     }
+                    // Context改变后，使用新的缓存，不再返回以前的Context调用的结果
                     pub fn context_mut(&mut self) -> &mut servant::Context {
+                        #cache_reset
+                        #parent_reset
                         &mut self.0
                     }
                     pub fn terminal(&self) -> servant::Terminal {
                         self.2.clone()
                     }
+                    pub fn batch(&self) -> #batch_ident {
+                        #batch_ident(self.clone(), Vec::new(), Vec::new())
+                    }
+                    #cache_invalidation
+                    #(
+                        pub fn #as_super_vec(&self) -> #super_proxy_vec {
+                            #super_proxy_vec::new(self.0.clone(), self.1.name(), &self.2)
+                        }
+                    )*
 
                     #(
                         pub async fn #fn_ident_vec(
                             &mut self,
                             #(#inputs_vec)*
                         ) -> servant::ServantResult<#output_vec> {
-                            let request = #request_ident_vec::#fn_ident_camel_vec { #(#args_vec)* };
-                            let response = self
-                                .2
-                                .invoke(Some(self.0.clone()), Some(self.1.clone()), bincode::serialize(&request).unwrap())
-                                .await;
-                            response.map(|v| bincode::deserialize(&v).unwrap())
+                            #trace_context
+                            #proxy_body_vec
                         }
                     )*
 
                     #output_callback
                 }
+
+                #output_deref
+
+                pub enum #response_ident {
+                    #(#fn_ident_camel_vec(servant::ServantResult<#output_vec>),)*
+                }
+
+                pub struct #batch_ident(#proxy_ident, Vec<#request_ident>, Vec<fn(&[u8]) -> #response_ident>);
+
+                impl #batch_ident {
+                    #(
+                    pub fn #fn_ident_vec(&mut self, #(#inputs_vec)*) -> &mut Self {
+                        self.1.push(#request_ident_vec::#fn_ident_camel_vec { #(#args_vec)* });
+                        self.2.push(|v| {
+                            #response_ident::#fn_ident_camel_vec(
+                                #batch_decode_vec
+                            )
+                        });
+                        self
+                    }
+                    )*
+                    pub async fn call(self) -> servant::ServantResult<Vec<#response_ident>> {
+                        let Self(proxy, requests, decoders) = self;
+                        if requests.is_empty() {
+                            return Ok(Vec::new());
+                        }
+                        let req = bincode::serialize(&#frame_ident::Batch(requests)).unwrap();
+                        #frame_check
+                        #compress_batch
+                        let (ctx, guard) = servant::CancelGuard::new(proxy.0.clone(), &proxy.2);
+                        #accept_context
+                        let response = proxy
+                            .2
+                            .invoke(Some(ctx), Some(proxy.1.clone()), req)
+                            .await;
+                        guard.disarm();
+                        let response = response?;
+                        let reps: Vec<Vec<u8>> = bincode::deserialize(&response)
+                            .map_err(|e| servant::ServantError::from(format!("invalid response: {}", e)))?;
+                        if reps.len() != decoders.len() {
+                            return Err(format!("{} responses expected, {} received.", decoders.len(), reps.len()).into());
+                        }
+                        #accept_batch
+                        Ok(reps.iter().zip(decoders).map(|(v, f)| f(v)).collect())
+                    }
+                }
             }
         } else {
             proc_macro2::TokenStream::new()
         };
 
+        // grpc = true时，生成protobuf消息和gRPC的adapter，与servant共享同一个实现类的对象
+        let output_grpc = if cfg!(feature = "server") && attributes.grpc.unwrap_or(false) {
+            protobuf::render_grpc(
+                &self.interface("invoke"),
+                trait_ident,
+                &servant_ident,
+                &frame_ident,
+                request_ident,
+                attributes.max_frame_size,
+            )
+        } else {
+            proc_macro2::TokenStream::new()
+        };
+        // http和jsonrpc feature：生成HTTP/JSON的handler和JSON-RPC的adapter，通过dispatch调用servant
+        let dispatch = quote! { #frame_ident::dispatch(&self.entity, &self.limiter, None, req, request_bytes) };
+        let output_gateway = gateway::render_gateway(
+            &servant_ident,
+            trait_ident,
+            request_ident,
+            fn_ident_vec,
+            fn_ident_camel_vec,
+            output_vec,
+            &dispatch,
+            true,
+            attributes.max_frame_size,
+        );
+        let output_export = self.render_export("invoke", attributes.idl_name.as_deref());
+
         let output = quote! {
             #output1
             #output2
             #output3
+            #output_grpc
+            #output_gateway
+            #output_export
         };
         output.into()
     }
@@ -634,11 +1913,14 @@ impl TraitContext {
             inputs_vec,
             // method_with_context_vec,
             method_vec,
+            method_attributes_vec,
             output_vec,
+            // entity_guard_vec,
             request_ident_vec,
             request_ident,
             ..
         } = self;
+        let descriptor = self.render_descriptor("Watch");
 
         let trait_ident = ident;
         let servant_ident = if let Some(ref s) = attributes.servant {
@@ -652,6 +1934,67 @@ impl TraitContext {
             format_ident!("{}Proxy", trait_ident)
         };
 
+        let unwind_guard = render_unwind_guard(attributes.catch_unwind.unwrap_or(true));
+        let cache = quote! { self.1 };
+        let invoke = quote! { self.0.invoke(None, None, req).await };
+        let call_vec: Vec<_> = method_attributes_vec
+            .iter()
+            .zip(fn_ident_vec.iter().zip(output_vec.iter()))
+            .map(|(a, (fn_ident, output))| {
+                render_cached_call(a.cache_ttl_ms, fn_ident, output, &cache, &invoke)
+            })
+            .collect();
+        let (cache_field, cache_init, cache_invalidation, _) =
+            render_cache_invalidation(method_attributes_vec, fn_ident_vec, &cache);
+        // tracing feature：watch接口没有Context，span中不记录trace id
+        let span_vec = self.render_span_vec(None);
+        let servant_enter_vec: Vec<_> = span_vec.iter().map(trace::render_enter).collect();
+        let trace_event = trace::render_event(&quote! { __servant_reps });
+        let trace_event_vec: Vec<_> = fn_ident_vec.iter().map(|_| trace_event.clone()).collect();
+        // metrics feature：记录每个方法调用的次数、字节数和耗时
+        let metrics_start = metrics::render_servant_start();
+        let metrics_record_vec = self.render_metrics_record_vec(true);
+        let servant_call_vec = self.render_servant_call_vec(|fn_ident, entity_guard, args| {
+            quote! {
+                self.entity
+                    .#entity_guard()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .#fn_ident(#(#args)*)
+            }
+        });
+        // 请求不合法时，servant返回ServantError，不调用实现类
+        let reply_error = quote! {
+            let reps: servant::ServantResult<()> = Err(e);
+            return bincode::serialize(&reps).unwrap();
+        };
+        let decode_request = render_decode(
+            attributes.max_frame_size,
+            &quote! { req },
+            &quote! { #request_ident },
+            &reply_error,
+        );
+        let frame_check = render_frame_check(attributes.max_frame_size);
+        let proxy_body_vec: Vec<_> = fn_ident_vec
+            .iter()
+            .zip(fn_ident_camel_vec.iter().zip(args_vec.iter()))
+            .zip(call_vec.iter().zip(span_vec.iter()))
+            .map(|((fn_ident, (fn_ident_camel, args)), (call, span))| {
+                let call = metrics::render_proxy(
+                    trait_ident,
+                    fn_ident,
+                    &quote! { req.len() as u64 },
+                    call,
+                );
+                let body = quote! {
+                    let request = #request_ident::#fn_ident_camel { #(#args)* };
+                    let req = bincode::serialize(&request).unwrap();
+                    #frame_check
+                    #call
+                };
+                trace::render_instrument(span, &body)
+            })
+            .collect();
+
         let output1 = if cfg!(any(feature = "server", feature = "client")) {
             quote! {
                 #[derive(serde::Serialize, serde::Deserialize)]
@@ -670,30 +2013,50 @@ impl TraitContext {
                     #(#method_vec)*
                 }
                 pub struct #servant_ident<S> {
-                    entity: S,
+                    entity: std::sync::RwLock<S>,
                 }
                 impl<S> #servant_ident<S> {
                     pub fn new(entity: S) -> Self {
-                        Self { entity }
+                        Self { entity: std::sync::RwLock::new(entity) }
                     }
+                    pub const DESCRIPTOR: servant::InterfaceDescriptor = #descriptor;
                     pub fn category() -> &'static str {
                         stringify!(#trait_ident)
                     }
+                    fn unwind_guard<T>(f: impl FnOnce() -> T) -> servant::ServantResult<T> {
+                        #unwind_guard
+                    }
                 }
-                impl<S> servant::WatchServant for #servant_ident<S>
+                impl<S> #servant_ident<S>
                 where
                     S: #trait_ident + 'static,
                 {
-                    fn serve(&mut self, req: Vec<u8>) -> Vec<u8> {
-                        let req: #request_ident = bincode::deserialize(&req).unwrap();
-                        let reps = match req {
+                    // 参数使用__servant_前缀，不会被同名的方法参数遮住；
+                    // __servant_request_bytes是收到的请求的字节数，用于metrics
+                    fn dispatch(&self, __servant_req: #request_ident, __servant_request_bytes: u64) -> Vec<u8> {
+                        #metrics_start
+                        match __servant_req {
                             #(
-                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } =>
-                                    bincode::serialize(&self.entity.#fn_ident_vec(#(#args_vec)*)),
+                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } => {
+                                    #servant_enter_vec
+                                    let __servant_reps = #servant_call_vec;
+                                    #metrics_record_vec
+                                    #trace_event_vec
+                                    bincode::serialize(&__servant_reps)
+                                }
                             )*
                         }
-                        .unwrap();
-                        reps
+                        .unwrap()
+                    }
+                }
+                impl<S> servant::WatchServant for #servant_ident<S>
+                where
+                    S: #trait_ident + 'static,
+                {
+                    fn serve(&self, req: Vec<u8>) -> Vec<u8> {
+                        let request_bytes = req.len() as u64;
+                        #decode_request
+                        self.dispatch(req, request_bytes)
                     }
                 }
             }
@@ -704,27 +2067,24 @@ impl TraitContext {
         let output3 = if cfg!(feature = "client") {
             quote! {
                 #[derive(Clone)]
-                pub struct #proxy_ident(servant::Terminal);
+                pub struct #proxy_ident(servant::Terminal #cache_field);
 
                 impl #proxy_ident {
                     pub fn new(t: &servant::Terminal) -> Self {
-                        Self(t.clone())
+                        Self(t.clone() #cache_init)
                     }
+                    pub const DESCRIPTOR: servant::InterfaceDescriptor = #descriptor;
                     pub fn category() -> &'static str {
                         stringify!(#ident)
                     }
+                    #cache_invalidation
 
                     #(
                     pub async fn #fn_ident_vec(
                         &mut self,
                         #(#inputs_vec)*
                     ) -> servant::ServantResult<#output_vec> {
-                        let request = #request_ident_vec::#fn_ident_camel_vec { #(#args_vec)* };
-                        let response = self
-                            .0
-                            .invoke(None, None, bincode::serialize(&request).unwrap())
-                            .await;
-                        response.map(|x| bincode::deserialize(&x).unwrap())
+                        #proxy_body_vec
                     }
                     )*
                 }
@@ -733,10 +2093,27 @@ impl TraitContext {
             proc_macro2::TokenStream::new()
         };
 
+        // http和jsonrpc feature：生成HTTP/JSON的handler和JSON-RPC的adapter，通过dispatch调用servant
+        let dispatch = quote! { self.dispatch(req, request_bytes) };
+        let output_gateway = gateway::render_gateway(
+            &servant_ident,
+            trait_ident,
+            request_ident,
+            fn_ident_vec,
+            fn_ident_camel_vec,
+            output_vec,
+            &dispatch,
+            false,
+            attributes.max_frame_size,
+        );
+        let output_export = self.render_export("watch", attributes.idl_name.as_deref());
+
         let output = quote! {
             #output1
             #output2
             #output3
+            #output_gateway
+            #output_export
         };
         output.into()
     }
@@ -768,10 +2145,12 @@ impl TraitContext {
             // method_with_context_vec,
             method_vec,
             // output_vec,
+            // entity_guard_vec,
             request_ident_vec,
             request_ident,
             ..
         } = self;
+        let descriptor = self.render_descriptor("Report");
 
         let trait_ident = ident;
         let servant_ident = if let Some(ref s) = attributes.servant {
@@ -784,18 +2163,147 @@ impl TraitContext {
         } else {
             format_ident!("{}Staff", trait_ident)
         };
+        let envelope_ident = format_ident!("{}Envelope", trait_ident);
+        let frame_ident = format_ident!("{}Frame", trait_ident);
+        let batch_ident = format_ident!("{}Batch", proxy_ident);
+        let at_least_once = attributes.delivery.as_deref() == Some(AT_LEAST_ONCE_STR);
 
+        let unwind_guard = render_unwind_guard(attributes.catch_unwind.unwrap_or(true));
+        // tracing feature：report接口没有Context，span中不记录trace id
+        let span_vec = self.render_span_vec(None);
+        let servant_enter_vec: Vec<_> = span_vec.iter().map(trace::render_enter).collect();
+        let trace_event = trace::render_event(&quote! { __servant_reps });
+        let trace_event_vec: Vec<_> = fn_ident_vec.iter().map(|_| trace_event.clone()).collect();
+        // metrics feature：记录每个方法调用的次数、字节数和耗时
+        let metrics_start = metrics::render_servant_start();
+        let metrics_record_vec = self.render_metrics_record_vec(false);
+        let servant_call_vec = self.render_servant_call_vec(|fn_ident, entity_guard, args| {
+            quote! {
+                self.entity
+                    .#entity_guard()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .#fn_ident(#(#args)*)
+            }
+        });
+        // 请求不合法时，officer与实现类的错误一样交给on_error处理，不调用实现类
+        let report_error = quote! {
+            self.report_error(e);
+            return;
+        };
+        let proxy_body_vec: Vec<_> = fn_ident_vec
+            .iter()
+            .zip(fn_ident_camel_vec.iter().zip(args_vec.iter()))
+            .zip(span_vec.iter())
+            .map(|((fn_ident, (fn_ident_camel, args)), span)| {
+                let call = metrics::render_proxy(
+                    trait_ident,
+                    fn_ident,
+                    &quote! { bincode::serialized_size(&request).unwrap_or_default() },
+                    &quote! { self.deliver(#frame_ident::Report(request)).await },
+                );
+                let body = quote! {
+                    let request = #request_ident::#fn_ident_camel { #(#args)* };
+                    #call
+                };
+                trace::render_instrument(span, &body)
+            })
+            .collect();
+
+        let output_envelope = if at_least_once {
+            quote! {
+                #[derive(serde::Serialize, serde::Deserialize)]
+                struct #envelope_ident {
+                    origin: u64,
+                    seq: u64,
+                    frame: #frame_ident,
+                }
+            }
+        } else {
+            proc_macro2::TokenStream::new()
+        };
         let output1 = if cfg!(any(feature = "server", feature = "client")) {
             quote! {
                 #[derive(serde::Serialize, serde::Deserialize)]
                 enum #request_ident {
                     #(#fn_ident_camel_vec { #(#inputs_vec)* },)*
                 }
+                #[derive(serde::Serialize, serde::Deserialize)]
+                enum #frame_ident {
+                    Report(#request_ident),
+                    Batch(Vec<#request_ident>),
+                }
+                #output_envelope
             }
         } else {
             proc_macro2::TokenStream::new()
         };
 
+        // at_least_once时，officer为每个staff记录收到的最大序号，以及之前128个序号是否收到过，
+        // 丢弃重复的报告；乱序到达的报告只要在这个窗口之内，仍然可以接收
+        let (dedup_field, dedup_init, dedup_accept, decode_request) = if at_least_once {
+            (
+                quote! {
+                    // (计数, origin => (最大序号, 窗口, 最后一次报告时的计数))
+                    seen: std::sync::Mutex<(u64, std::collections::HashMap<u64, (u64, u128, u64)>)>,
+                },
+                quote! {
+                    seen: std::sync::Mutex::new((0, std::collections::HashMap::new())),
+                },
+                quote! {
+                    fn accept(&self, origin: u64, seq: u64) -> bool {
+                        let mut seen = self.seen.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+                        let (tick, origins) = &mut *seen;
+                        *tick += 1;
+                        if !origins.contains_key(&origin) && origins.len() >= #DEDUP_ORIGINS {
+                            let oldest = origins.iter().min_by_key(|(_, v)| v.2).map(|(k, _)| *k);
+                            if let Some(oldest) = oldest {
+                                origins.remove(&oldest);
+                            }
+                        }
+                        let (high, window, used) = origins.entry(origin).or_insert((seq, 0, 0));
+                        *used = *tick;
+                        if seq > *high {
+                            let shift = seq - *high;
+                            *window = if shift >= 128 { 0 } else { *window << shift };
+                            *high = seq;
+                        }
+                        let offset = *high - seq;
+                        if offset >= 128 || *window & (1u128 << offset) != 0 {
+                            return false;
+                        }
+                        *window |= 1u128 << offset;
+                        true
+                    }
+                },
+                {
+                    let decode_envelope = render_decode(
+                        attributes.max_frame_size,
+                        &quote! { #envelope_ident { origin, seq, frame } },
+                        &quote! { #envelope_ident },
+                        &report_error,
+                    );
+                    quote! {
+                        #decode_envelope
+                        if !self.accept(origin, seq) {
+                            return;
+                        }
+                    }
+                },
+            )
+        } else {
+            (
+                proc_macro2::TokenStream::new(),
+                proc_macro2::TokenStream::new(),
+                proc_macro2::TokenStream::new(),
+                render_decode(
+                    attributes.max_frame_size,
+                    &quote! { frame },
+                    &quote! { #frame_ident },
+                    &report_error,
+                ),
+            )
+        };
+        let log_error = trace::render_error(trait_ident, Some(&quote! { self.name }));
         let output2 = if cfg!(feature = "server") {
             quote! {
                 #( #attrs )*
@@ -804,15 +2312,64 @@ impl TraitContext {
                 }
                 pub struct #servant_ident<S> {
                     name: String,
-                    entity: S,
+                    entity: std::sync::RwLock<S>,
+                    on_error: Option<Box<dyn Fn(&servant::ServantError) + Send + Sync>>,
+                    #dedup_field
                 }
                 impl<S> #servant_ident<S> {
                     pub fn new(name: &str, entity: S) -> Self {
-                        Self { name: name.to_string(), entity }
+                        Self {
+                            name: name.to_string(),
+                            entity: std::sync::RwLock::new(entity),
+                            on_error: None,
+                            #dedup_init
+                        }
+                    }
+                    // 实现类的方法panic时，错误不能返回给staff，交给f处理
+                    pub fn on_error<F>(mut self, f: F) -> Self
+                    where
+                        F: Fn(&servant::ServantError) + Send + Sync + 'static,
+                    {
+                        self.on_error = Some(Box::new(f));
+                        self
                     }
+                    pub const DESCRIPTOR: servant::InterfaceDescriptor = #descriptor;
                     pub fn category() -> &'static str {
                         stringify!(#trait_ident)
                     }
+                    fn unwind_guard<T>(f: impl FnOnce() -> T) -> servant::ServantResult<T> {
+                        #unwind_guard
+                    }
+                    fn report_error(&self, e: servant::ServantError) {
+                        if let Some(f) = &self.on_error {
+                            f(&e);
+                        } else {
+                            #log_error
+                        }
+                    }
+                    #dedup_accept
+                    // 参数使用__servant_前缀，不会被同名的方法参数遮住；
+                    // __servant_request_bytes是收到的请求的字节数，用于metrics
+                    fn dispatch(&self, __servant_req: #request_ident, __servant_request_bytes: u64)
+                    where
+                        S: #trait_ident,
+                    {
+                        #metrics_start
+                        let reps = match __servant_req {
+                            #(
+                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } => {
+                                    #servant_enter_vec
+                                    let __servant_reps = #servant_call_vec;
+                                    #metrics_record_vec
+                                    #trace_event_vec
+                                    __servant_reps
+                                }
+                            )*
+                        };
+                        if let Err(e) = reps {
+                            self.report_error(e);
+                        }
+                    }
                 }
                 impl<S> servant::ReportServant for #servant_ident<S>
                 where
@@ -821,13 +2378,16 @@ impl TraitContext {
                     fn name(&self) -> &str {
                         &self.name
                     }
-                    fn serve(&mut self, req: Vec<u8>) {
-                        let req: #request_ident = bincode::deserialize(&req).unwrap();
-                        match req {
-                            #(
-                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } =>
-                                    self.entity.#fn_ident_vec(#(#args_vec)*),
-                            )*
+                    fn serve(&self, req: Vec<u8>) {
+                        let request_bytes = req.len() as u64;
+                        #decode_request
+                        match frame {
+                            #frame_ident::Report(req) => self.dispatch(req, request_bytes),
+                            #frame_ident::Batch(reqs) => {
+                                // batch中的每个请求记录平均的字节数
+                                let request_bytes = request_bytes / reqs.len().max(1) as u64;
+                                reqs.into_iter().for_each(|req| self.dispatch(req, request_bytes))
+                            }
                         }
                     }
                 }
@@ -836,7 +2396,60 @@ impl TraitContext {
             proc_macro2::TokenStream::new()
         };
 
-        let output3 = if cfg!(feature = "client") {
+        let output_delivery = if at_least_once {
+            let outbox_capacity = attributes
+                .outbox_capacity
+                .unwrap_or(DEFAULT_OUTBOX_CAPACITY);
+            quote! {
+                // clone的staff共享序号和发送的锁
+                #[derive(Clone)]
+                pub struct #proxy_ident(
+                    servant::Oid,
+                    servant::Terminal,
+                    servant::Outbox,
+                    std::sync::Arc<std::sync::atomic::AtomicU64>,
+                    std::sync::Arc<futures::lock::Mutex<()>>,
+                );
+
+                impl #proxy_ident {
+                    pub fn new(name: &str, t: &servant::Terminal) -> Self {
+                        Self::with_outbox(name, t, servant::Outbox::memory(#outbox_capacity))
+                    }
+                    // outbox的origin和已经使用的最大序号在重启之后不变，新的报告从下一个序号开始
+                    pub fn with_outbox(name: &str, t: &servant::Terminal, outbox: servant::Outbox) -> Self {
+                        let oid = servant::Oid::new(name, stringify!(#trait_ident));
+                        let next = outbox.last_seq().map_or(0, |seq| seq + 1);
+                        let next = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(next));
+                        Self(oid, t.clone(), outbox, next, Default::default())
+                    }
+                    // 按照序号发送outbox中的报告，直到outbox为空或者发送失败；staff不会自动重试，
+                    // 连接恢复之后需要调用flush
+                    pub async fn flush(&mut self) -> servant::ServantResult<()> {
+                        let _flushing = self.4.lock().await;
+                        self.send_outbox().await
+                    }
+                    // 调用者持有发送的锁
+                    async fn send_outbox(&self) -> servant::ServantResult<()> {
+                        while let Some((seq, req)) = self.2.peek() {
+                            self.1.report_acked(self.0.clone(), req).await?;
+                            self.2.remove(seq);
+                        }
+                        Ok(())
+                    }
+                    // 在同一个锁中分配序号和放入outbox，outbox中的报告按照序号排列；outbox满时不使用这个序号。
+                    // 报告放入outbox之后就返回Ok，发送失败的报告保留在outbox中，之后的报告或者flush重新发送
+                    async fn deliver(&mut self, frame: #frame_ident) -> servant::ServantResult<()> {
+                        let _flushing = self.4.lock().await;
+                        let seq = self.3.load(std::sync::atomic::Ordering::SeqCst);
+                        let envelope = #envelope_ident { origin: self.2.origin(), seq, frame };
+                        self.2.push(seq, bincode::serialize(&envelope).unwrap())?;
+                        self.3.store(seq + 1, std::sync::atomic::Ordering::SeqCst);
+                        let _ = self.send_outbox().await;
+                        Ok(())
+                    }
+                }
+            }
+        } else {
             quote! {
                 #[derive(Clone)]
                 pub struct #proxy_ident(servant::Oid, servant::Terminal);
@@ -846,33 +2459,66 @@ impl TraitContext {
                         let oid = servant::Oid::new(name, stringify!(#trait_ident));
                         Self(oid, t.clone())
                     }
+                    async fn deliver(&mut self, frame: #frame_ident) -> servant::ServantResult<()> {
+                        self.1
+                            .report(self.0.clone(), bincode::serialize(&frame).unwrap())
+                            .await
+                    }
+                }
+            }
+        };
+        let output3 = if cfg!(feature = "client") {
+            quote! {
+                #output_delivery
+
+                impl #proxy_ident {
+                    pub const DESCRIPTOR: servant::InterfaceDescriptor = #descriptor;
                     pub fn category() -> &'static str {
                         stringify!(#trait_ident)
                     }
+                    pub fn batch(&self) -> #batch_ident {
+                        #batch_ident(self.clone(), Vec::new())
+                    }
 
                     #(
                     pub async fn #fn_ident_vec(
                         &mut self,
                         #(#inputs_vec)*
                     ) -> servant::ServantResult<()> {
-                        let request = #request_ident_vec::#fn_ident_camel_vec { #(#args_vec)* };
-                        let response = self
-                            .1
-                            .report(self.0.clone(), bincode::serialize(&request).unwrap())
-                            .await;
-                        response
+                        #proxy_body_vec
                     }
                     )*
                 }
+
+                pub struct #batch_ident(#proxy_ident, Vec<#request_ident>);
+
+                impl #batch_ident {
+                    #(
+                    pub fn #fn_ident_vec(&mut self, #(#inputs_vec)*) -> &mut Self {
+                        self.1.push(#request_ident_vec::#fn_ident_camel_vec { #(#args_vec)* });
+                        self
+                    }
+                    )*
+                    pub async fn send(self) -> servant::ServantResult<()> {
+                        let Self(mut staff, requests) = self;
+                        if requests.is_empty() {
+                            return Ok(());
+                        }
+                        staff.deliver(#frame_ident::Batch(requests)).await
+                    }
+                }
             }
         } else {
             proc_macro2::TokenStream::new()
         };
 
+        let output_export = self.render_export("report", attributes.idl_name.as_deref());
+
         let output = quote! {
             #output1
             #output2
             #output3
+            #output_export
         };
         output.into()
     }
@@ -908,6 +2554,7 @@ impl TraitContext {
             request_ident,
             ..
         } = self;
+        let descriptor = self.render_descriptor("Notify");
 
         let trait_ident = ident;
         let receiver_ident = if let Some(ref s) = attributes.receiver {
@@ -920,6 +2567,65 @@ impl TraitContext {
         } else {
             format_ident!("{}Notifier", trait_ident)
         };
+        let subscription_ident = format_ident!("{}Subscription", trait_ident);
+        let event_ident = format_ident!("{}Event", trait_ident);
+        let event_sender_ident = format_ident!("{}EventSender", trait_ident);
+        let stream_ident = format_ident!("{}Stream", trait_ident);
+
+        let unwind_guard = render_unwind_guard(attributes.catch_unwind.unwrap_or(true));
+        // tracing feature：notify接口没有Context，span中不记录trace id
+        let span_vec = self.render_span_vec(None);
+        let receiver_enter_vec: Vec<_> = span_vec.iter().map(trace::render_enter).collect();
+        let trace_event = trace::render_event(&quote! { __servant_reps });
+        let trace_event_vec: Vec<_> = fn_ident_vec.iter().map(|_| trace_event.clone()).collect();
+        // metrics feature：记录每个方法调用的次数、字节数和耗时
+        let metrics_start = metrics::render_servant_start();
+        let metrics_record_vec = self.render_metrics_record_vec(false);
+        let receiver_call_vec = self.render_servant_call_vec(|fn_ident, _, args| {
+            quote! { __servant_entity.#fn_ident(#(#args)*) }
+        });
+        // 请求不合法时，receiver与实现类的错误一样交给on_error处理，不调用实现类
+        let receiver_error = quote! {
+            Self::report_error(&self.on_error, e);
+            return;
+        };
+        // event sender没有on_error，通知不合法时只在tracing feature输出error event
+        let log_error = trace::render_error(trait_ident, None);
+        let event_error = quote! {
+            #log_error
+            return;
+        };
+        let decode_request = render_decode(
+            attributes.max_frame_size,
+            &quote! { req },
+            &quote! { #request_ident },
+            &receiver_error,
+        );
+        let decode_event = render_decode(
+            attributes.max_frame_size,
+            &quote! { event },
+            &quote! { #event_ident },
+            &event_error,
+        );
+        let notifier_body_vec: Vec<_> = fn_ident_vec
+            .iter()
+            .zip(fn_ident_camel_vec.iter().zip(args_vec.iter()))
+            .zip(span_vec.iter())
+            .map(|((fn_ident, (fn_ident_camel, args)), span)| {
+                let call = metrics::render_proxy(
+                    trait_ident,
+                    fn_ident,
+                    &quote! { req.len() as u64 },
+                    &quote! { self.0.send_to(self.1.clone(), request.topic(), req).await },
+                );
+                let body = quote! {
+                    let request = #request_ident::#fn_ident_camel { #(#args)* };
+                    let req = bincode::serialize(&request).unwrap();
+                    #call
+                };
+                trace::render_instrument(span, &body)
+            })
+            .collect();
 
         let output1 = if cfg!(any(feature = "server", feature = "client")) {
             quote! {
@@ -932,6 +2638,40 @@ impl TraitContext {
             proc_macro2::TokenStream::new()
         };
 
+        let output_stream = if attributes.stream.unwrap_or(false) {
+            quote! {
+                #[derive(serde::Deserialize)]
+                pub enum #event_ident {
+                    #(#fn_ident_camel_vec { #(#inputs_vec)* },)*
+                }
+                pub struct #event_sender_ident(futures::channel::mpsc::UnboundedSender<#event_ident>);
+                impl servant::NotifyServant for #event_sender_ident {
+                    fn serve(&mut self, req: Vec<u8>) {
+                        // event和request的定义相同，可以直接反序列化
+                        #decode_event
+                        let _ = self.0.unbounded_send(event);
+                    }
+                }
+                pub struct #stream_ident(futures::channel::mpsc::UnboundedReceiver<#event_ident>);
+                impl #stream_ident {
+                    pub fn channel() -> (#event_sender_ident, Self) {
+                        let (tx, rx) = futures::channel::mpsc::unbounded();
+                        (#event_sender_ident(tx), Self(rx))
+                    }
+                }
+                impl futures::Stream for #stream_ident {
+                    type Item = #event_ident;
+                    fn poll_next(
+                        mut self: std::pin::Pin<&mut Self>,
+                        cx: &mut std::task::Context<'_>,
+                    ) -> std::task::Poll<Option<Self::Item>> {
+                        futures::Stream::poll_next(std::pin::Pin::new(&mut self.0), cx)
+                    }
+                }
+            }
+        } else {
+            proc_macro2::TokenStream::new()
+        };
         let output2 = if cfg!(feature = "client") {
             quote! {
                 #( #attrs )*
@@ -940,10 +2680,34 @@ impl TraitContext {
                 }
                 pub struct #receiver_ident<S> {
                     entity: S,
+                    on_error: Option<Box<dyn Fn(&servant::ServantError) + Send>>,
                 }
                 impl<S> #receiver_ident<S> {
+                    pub const DESCRIPTOR: servant::InterfaceDescriptor = #descriptor;
                     pub fn new(entity: S) -> Self {
-                        Self { entity }
+                        Self { entity, on_error: None }
+                    }
+                    // 实现类的方法panic时，错误不能返回给notifier，交给f处理
+                    pub fn on_error<F>(mut self, f: F) -> Self
+                    where
+                        F: Fn(&servant::ServantError) + Send + 'static,
+                    {
+                        self.on_error = Some(Box::new(f));
+                        self
+                    }
+                    fn unwind_guard<T>(f: impl FnOnce() -> T) -> servant::ServantResult<T> {
+                        #unwind_guard
+                    }
+                    // 与entity的借用不冲突，只借用on_error
+                    fn report_error(
+                        on_error: &Option<Box<dyn Fn(&servant::ServantError) + Send>>,
+                        e: servant::ServantError,
+                    ) {
+                        if let Some(f) = on_error {
+                            f(&e);
+                        } else {
+                            #log_error
+                        }
                     }
                 }
                 impl<S> servant::NotifyServant for #receiver_ident<S>
@@ -951,15 +2715,48 @@ impl TraitContext {
                     S: #ident + 'static + Send,
                 {
                     fn serve(&mut self, req: Vec<u8>) {
-                        let req: #request_ident = bincode::deserialize(&req).unwrap();
-                        match req {
+                        // 局部变量使用__servant_前缀，不会被同名的方法参数遮住
+                        let __servant_request_bytes = req.len() as u64;
+                        #decode_request
+                        let __servant_entity = &mut self.entity;
+                        #metrics_start
+                        let reps = match req {
                             #(
-                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } =>
-                                    self.entity.#fn_ident_vec(#(#args_vec)*),
+                                #request_ident_vec::#fn_ident_camel_vec{ #(#args_vec)* } => {
+                                    #receiver_enter_vec
+                                    let __servant_reps = #receiver_call_vec;
+                                    #metrics_record_vec
+                                    #trace_event_vec
+                                    __servant_reps
+                                }
                             )*
+                        };
+                        if let Err(e) = reps {
+                            Self::report_error(&self.on_error, e);
                         }
                     }
                 }
+
+                pub struct #subscription_ident(servant::Terminal, Vec<&'static str>);
+                impl #subscription_ident {
+                    pub fn new(t: &servant::Terminal) -> Self {
+                        Self(t.clone(), Vec::new())
+                    }
+                    pub fn all(mut self) -> Self {
+                        self.1 = vec![#(stringify!(#fn_ident_vec),)*];
+                        self
+                    }
+                    #(
+                    pub fn #fn_ident_vec(mut self) -> Self {
+                        self.1.push(stringify!(#fn_ident_vec));
+                        self
+                    }
+                    )*
+                    pub async fn subscribe(self) -> servant::ServantResult<()> {
+                        self.0.subscribe(stringify!(#ident), &self.1).await
+                    }
+                }
+                #output_stream
             }
         } else {
             proc_macro2::TokenStream::new()
@@ -967,22 +2764,32 @@ impl TraitContext {
 
         let output3 = if cfg!(feature = "server") {
             quote! {
+                impl #request_ident {
+                    fn topic(&self) -> &'static str {
+                        match self {
+                            #(#request_ident_vec::#fn_ident_camel_vec{ .. } => stringify!(#fn_ident_vec),)*
+                        }
+                    }
+                }
                 #[derive(Clone)]
-                pub struct #notifier_ident(servant::AdapterRegister);
+                pub struct #notifier_ident(servant::AdapterRegister, servant::Recipient);
                 impl #notifier_ident {
+                    pub const DESCRIPTOR: servant::InterfaceDescriptor = #descriptor;
                     pub fn new(ar: servant::AdapterRegister) -> Self {
-                        Self(ar)
+                        Self(ar, servant::Recipient::All)
+                    }
+                    pub fn to(&self, client_id: servant::ClientId) -> Self {
+                        Self(self.0.clone(), servant::Recipient::Client(client_id))
+                    }
+                    pub fn to_group(&self, name: &str) -> Self {
+                        Self(self.0.clone(), servant::Recipient::Group(name.to_string()))
                     }
                     #(
                     pub async fn #fn_ident_vec(
                         &self,
                         #(#inputs_vec)*
-                    )  {
-                        let request = #request_ident_vec::#fn_ident_camel_vec { #(#args_vec)* };
-                        self
-                            .0
-                            .send(bincode::serialize(&request).unwrap())
-                            .await
+                    ) -> servant::ServantResult<()> {
+                        #notifier_body_vec
                     }
                     )*
                 }
@@ -991,15 +2798,444 @@ impl TraitContext {
             proc_macro2::TokenStream::new()
         };
 
+        let output_export = self.render_export("notify", attributes.idl_name.as_deref());
+
         let output = quote! {
             #output1
             #output2
             #output3
+            #output_export
         };
         output.into()
     }
 }
 
+// --
+
+#[cfg_attr(test, derive(Debug))]
+pub(crate) struct ComposeAttributes {
+    interfaces: Vec<Path>,
+}
+impl Parse for ComposeAttributes {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let args = Punctuated::<Path, Comma>::parse_terminated(input)?;
+        if args.is_empty() {
+            Err(Error::new(input.span(), "invoke interfaces expected."))?;
+        }
+        Ok(Self {
+            interfaces: args.into_iter().collect(),
+        })
+    }
+}
+
+// --
+
+#[cfg_attr(test, derive(Debug))]
+pub(crate) struct ComposeContext {
+    item_struct: ItemStruct,
+}
+impl Parse for ComposeContext {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let item_struct: ItemStruct = input.parse()?;
+        if !item_struct.generics.params.is_empty() {
+            Err(Error::new(
+                item_struct.generics.span(),
+                "generic struct is not supported.",
+            ))?;
+        }
+        Ok(Self { item_struct })
+    }
+}
+
+impl ComposeContext {
+    pub(crate) fn render_compose(&self, attributes: &ComposeAttributes) -> TokenStream {
+        let ComposeContext { item_struct } = self;
+
+        let struct_ident = &item_struct.ident;
+        let servant_ident = format_ident!("{}Servant", struct_ident);
+        // 每个invoke接口生成的XxxFrame，与接口定义在同一个模块中
+        let frame_path_vec: Vec<_> = attributes
+            .interfaces
+            .iter()
+            .map(|p| replace_last_ident(p, "Frame"))
+            .collect();
+
+        let output1 = if cfg!(feature = "server") {
+            quote! {
+                pub struct #servant_ident {
+                    name: String,
+                    entity: std::sync::RwLock<#struct_ident>,
+                    limiter: servant::RateLimiter,
+                }
+                impl #servant_ident {
+                    pub fn new(name: &str, entity: #struct_ident) -> Self {
+                        Self {
+                            name: name.to_string(),
+                            entity: std::sync::RwLock::new(entity),
+                            limiter: servant::RateLimiter::default(),
+                        }
+                    }
+                    pub fn categories() -> Vec<&'static str> {
+                        let mut categories = Vec::new();
+                        #( categories.extend(#frame_path_vec::categories()); )*
+                        categories
+                    }
+                }
+                impl servant::CompositeServant for #servant_ident {
+                    fn name(&self) -> &str {
+                        &self.name
+                    }
+                    fn categories(&self) -> Vec<&'static str> {
+                        Self::categories()
+                    }
+                    fn serve(&self, category: &str, ctx: Option<servant::Context>, req: Vec<u8>) -> Vec<u8> {
+                        #(
+                            if #frame_path_vec::categories().contains(&category) {
+                                return #frame_path_vec::serve_category(&self.entity, &self.limiter, category, ctx, req).unwrap();
+                            }
+                        )*
+                        let reps: servant::ServantResult<()> =
+                            Err(format!("category '{}' is not served.", category).into());
+                        bincode::serialize(&reps).unwrap()
+                    }
+                }
+            }
+        } else {
+            proc_macro2::TokenStream::new()
+        };
+
+        let output = quote! {
+            #item_struct
+            #output1
+        };
+        output.into()
+    }
+}
+
+// --
+
+// 合并#[doc = "..."]，即文档注释的内容
+pub(crate) fn doc_string(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident(DOC_STR))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(s), ..
+            })) => Some(s.value().trim().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 在路径最后的ident后增加后缀，例如a::Hello => a::HelloFrame
+fn replace_last_ident(path: &Path, suffix: &str) -> Path {
+    let mut path = path.clone();
+    let last = path.segments.last_mut().unwrap();
+    last.ident = format_ident!("{}{}", last.ident, suffix);
+    path
+}
+
+// rate_limit的方法被限流时，服务端没有执行这个方法，proxy按照返回的等待时间重试，最多retry次
+fn render_throttled_call(
+    rate_limit: Option<(u64, u64)>,
+    retry: usize,
+    call: TokenStream2,
+) -> TokenStream2 {
+    if rate_limit.is_none() {
+        return call;
+    }
+    quote! {
+        let mut throttled = 0;
+        loop {
+            let req = req.clone();
+            let reps = async { #call }.await;
+            if let Err(e) = &reps {
+                if let Some(retry_after_ms) = e.retry_after_ms() {
+                    if throttled < #retry {
+                        throttled += 1;
+                        servant::sleep(std::time::Duration::from_millis(retry_after_ms)).await;
+                        continue;
+                    }
+                }
+            }
+            break reps;
+        }
+    }
+}
+
+// 服务端反序列化请求，解码失败时以ServantError执行on_error；设置了max_frame_size时，先检查请求的大小，
+// 再按照这个大小限制反序列化，避免请求中的长度导致分配过多的内存
+fn render_decode(
+    max_frame_size: Option<usize>,
+    pat: &TokenStream2,
+    ty: &TokenStream2,
+    on_error: &TokenStream2,
+) -> TokenStream2 {
+    // 与bincode::deserialize的编码相同，只是增加了大小限制
+    let decode = if let Some(max) = max_frame_size {
+        quote! {
+            if req.len() > #max {
+                Err(format!("frame size {} exceeds max_frame_size {}.", req.len(), #max))
+            } else {
+                use bincode::Options;
+                bincode::DefaultOptions::new()
+                    .with_fixint_encoding()
+                    .allow_trailing_bytes()
+                    .with_limit(#max as u64)
+                    .deserialize::<#ty>(&req)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    } else {
+        quote! {
+            bincode::deserialize::<#ty>(&req).map_err(|e| e.to_string())
+        }
+    };
+    quote! {
+        let decoded: Result<#ty, String> = #decode;
+        let #pat = match decoded {
+            Ok(v) => v,
+            Err(e) => {
+                let e = servant::ServantError::from(format!("invalid frame: {}", e));
+                #on_error
+            }
+        };
+    }
+}
+
+// max_frame_size的接口，proxy不发送超过大小的请求，直接返回错误
+fn render_frame_check(max_frame_size: Option<usize>) -> TokenStream2 {
+    if let Some(max) = max_frame_size {
+        quote! {
+            if req.len() > #max {
+                return Err(format!("frame size {} exceeds max_frame_size {}.", req.len(), #max).into());
+            }
+        }
+    } else {
+        proc_macro2::TokenStream::new()
+    }
+}
+
+// 调用实现类之前的检查，值是ServantResult<()>：rate_limit的方法按照Context中对端的地址检查调用的次数，
+// 超过时是throttled错误；validate的方法以解码后参数的引用调用validate函数。没有检查时是None
+fn render_precheck(
+    trait_ident: &Ident,
+    fn_ident: &Ident,
+    rate_limit: Option<(u64, u64)>,
+    validate: Option<&Path>,
+    args: &[TokenStream2],
+) -> Option<TokenStream2> {
+    let rate_limit = rate_limit.map(|(count, period_ms)| {
+        quote! {
+            __servant_limiter
+                .acquire(
+                    concat!(stringify!(#trait_ident), ".", stringify!(#fn_ident)),
+                    #count,
+                    #period_ms,
+                    __servant_ctx.as_ref().and_then(|c| c.peer()).map(|p| p.ip()),
+                )
+                .map_err(servant::ServantError::throttled)
+        }
+    });
+    let validate = validate.map(|validate| quote! { #validate(#(&#args)*) });
+    match (rate_limit, validate) {
+        (Some(rate_limit), Some(validate)) => Some(quote! { #rate_limit.and_then(|_| #validate) }),
+        (rate_limit, validate) => rate_limit.or(validate),
+    }
+}
+
+// cache_ttl_ms的方法，以方法名和序列化后的请求作为key，在proxy中缓存成功的结果。
+// 缓存的值是(过期时间, 最后使用的时间, 结果)，插入时先清除过期的结果，缓存满时淘汰最久没有使用的结果
+fn render_cached_call(
+    cache_ttl_ms: Option<u64>,
+    fn_ident: &Ident,
+    output: &TokenStream2,
+    cache: &TokenStream2,
+    invoke: &TokenStream2,
+) -> TokenStream2 {
+    if let Some(ttl) = cache_ttl_ms {
+        quote! {
+            let key = (stringify!(#fn_ident), req.clone());
+            let cached = {
+                let mut cache = #cache.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+                let now = std::time::Instant::now();
+                match cache.get_mut(&key) {
+                    Some((expires, used, v)) if *expires > now => {
+                        *used = now;
+                        Some(v.clone())
+                    }
+                    _ => None,
+                }
+            };
+            if let Some(v) = cached {
+                return bincode::deserialize::<servant::ServantResult<#output>>(&v).unwrap();
+            }
+            let response = { #invoke };
+            response.and_then(|v| {
+                let r = bincode::deserialize::<servant::ServantResult<#output>>(&v).unwrap();
+                if r.is_ok() {
+                    let mut cache = #cache.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+                    let now = std::time::Instant::now();
+                    cache.retain(|_, (expires, _, _)| *expires > now);
+                    if cache.len() >= #CACHE_CAPACITY {
+                        let lru = cache
+                            .iter()
+                            .min_by_key(|(_, (_, used, _))| *used)
+                            .map(|(k, _)| k.clone());
+                        if let Some(k) = lru {
+                            cache.remove(&k);
+                        }
+                    }
+                    cache.insert(key, (now + std::time::Duration::from_millis(#ttl), now, v));
+                }
+                r
+            })
+        }
+    } else {
+        quote! {
+            let response = { #invoke };
+            response.and_then(|v| bincode::deserialize::<servant::ServantResult<#output>>(&v).unwrap())
+        }
+    }
+}
+
+// 返回proxy中缓存的字段、初始化、invalidate_xxx方法，以及替换缓存的语句
+fn render_cache_invalidation(
+    method_attributes_vec: &[MethodAttributes],
+    fn_ident_vec: &[Ident],
+    cache: &TokenStream2,
+) -> (TokenStream2, TokenStream2, TokenStream2, TokenStream2) {
+    let cached_fn_ident_vec: Vec<_> = method_attributes_vec
+        .iter()
+        .zip(fn_ident_vec.iter())
+        .filter(|(a, _)| a.cache_ttl_ms.is_some())
+        .map(|(_, i)| i)
+        .collect();
+    if cached_fn_ident_vec.is_empty() {
+        return (
+            proc_macro2::TokenStream::new(),
+            proc_macro2::TokenStream::new(),
+            proc_macro2::TokenStream::new(),
+            proc_macro2::TokenStream::new(),
+        );
+    }
+    let invalidate_ident_vec: Vec<_> = cached_fn_ident_vec
+        .iter()
+        .map(|x| format_ident!("invalidate_{}", x))
+        .collect();
+    (
+        quote! {
+            , std::sync::Arc<std::sync::Mutex<std::collections::HashMap<
+                (&'static str, Vec<u8>),
+                (std::time::Instant, std::time::Instant, Vec<u8>),
+            >>>
+        },
+        quote! {
+            , Default::default()
+        },
+        quote! {
+            pub fn invalidate_cache(&self) {
+                #cache.lock().unwrap_or_else(std::sync::PoisonError::into_inner).clear();
+            }
+            #(
+            pub fn #invalidate_ident_vec(&self) {
+                #cache
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .retain(|k, _| k.0 != stringify!(#cached_fn_ident_vec));
+            }
+            )*
+        },
+        quote! {
+            #cache = Default::default();
+        },
+    )
+}
+
+fn render_unwind_guard(catch_unwind: bool) -> TokenStream2 {
+    if catch_unwind {
+        quote! {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|e| {
+                let msg = if let Some(s) = e.downcast_ref::<&str>() {
+                    s.to_string()
+                } else if let Some(s) = e.downcast_ref::<String>() {
+                    s.clone()
+                } else {
+                    "unknown panic".to_string()
+                };
+                format!("servant panicked: {}", msg).into()
+            })
+        }
+    } else {
+        quote! {
+            Ok(f())
+        }
+    }
+}
+
+// --
+
+#[cfg(test)]
+mod tests {
+    extern crate test_case;
+    use super::*;
+    use test_case::test_case;
+
+    // --
+
+    #[test_case("notify", "trait A { fn all(&self); }" => Err("method name 'all' is reserved in notify interface.".to_string()); "notify all")]
+    #[test_case("notify", "trait A { fn to(&self, id: u64); }" => Err("method name 'to' is reserved in notify interface.".to_string()); "notify to")]
+    #[test_case("notify", "trait A { fn date(&self); }" => Ok(()); "notify ok")]
+    #[test_case("invoke", "trait A { fn all(&self) -> u8; }" => Ok(()); "invoke all")]
+    #[test_case("watch", "trait A { #[servant(idempotent)] fn a(&self) -> u8; }" => Err("'idempotent' is used in invoke interface only.".to_string()); "watch idempotent")]
+    #[test_case("report", "trait A { #[servant(idempotent)] fn a(&self); }" => Err("'idempotent' is used in invoke interface only.".to_string()); "report idempotent")]
+    #[test_case("invoke", "trait A { #[servant(idempotent)] fn a(&self) -> u8; }" => Ok(()); "invoke idempotent")]
+    #[test_case("watch", "trait A { #[servant(rate_limit = \"1/s\")] fn a(&self) -> u8; }" => Err("'rate_limit' is used in invoke interface only.".to_string()); "watch rate_limit")]
+    #[test_case("report", "trait A { #[servant(rate_limit = \"1/s\")] fn a(&self); }" => Err("'rate_limit' is used in invoke interface only.".to_string()); "report rate_limit")]
+    #[test_case("notify", "trait A { #[servant(rate_limit = \"1/s\")] fn a(&self); }" => Err("'rate_limit' is used in invoke interface only.".to_string()); "notify rate_limit")]
+    #[test_case("invoke", "trait A { #[servant(rate_limit = \"1/s\")] fn a(&self) -> u8; }" => Ok(()); "invoke rate_limit")]
+    #[test_case("watch", "trait A { #[servant(compress = \"lz4\")] fn a(&self) -> u8; }" => Err("'compress' is used in invoke interface only.".to_string()); "watch compress")]
+    #[test_case("report", "trait A { #[servant(compress = \"zstd\")] fn a(&self); }" => Err("'compress' is used in invoke interface only.".to_string()); "report compress")]
+    #[test_case("invoke", "trait A { #[servant(compress = \"zstd\")] fn a(&self) -> u8; }" => Ok(()); "invoke compress")]
+    #[test_case("report", "trait A { #[servant(cache_ttl_ms = 10)] fn a(&self); }" => Err("'cache_ttl_ms' is used in invoke and watch interface only.".to_string()); "report cache")]
+    #[test_case("notify", "trait A { #[servant(cache_ttl_ms = 10)] fn a(&self); }" => Err("'cache_ttl_ms' is used in invoke and watch interface only.".to_string()); "notify cache")]
+    #[test_case("watch", "trait A { #[servant(cache_ttl_ms = 10)] fn a(&self) -> u8; }" => Ok(()); "watch cache")]
+    #[test_case("watch", "trait A { fn invalidate_cache(&self) -> u8; }" => Err("method name 'invalidate_cache' is reserved in watch interface.".to_string()); "watch invalidate_cache")]
+    #[test_case("invoke", "trait A { #[servant(cache_ttl_ms = 10)] fn a(&self) -> u8; fn invalidate_a(&self); }" => Err("method name 'invalidate_a' is reserved in invoke interface.".to_string()); "invoke invalidate_a")]
+    #[test_case("invoke", "trait A { fn a(&self, mut n: u8); }" => Err("parameter expected an identifier, such as 'n: i32'.".to_string()); "mut param")]
+    #[test_case("notify", "trait A { fn a(&self, _: u8); }" => Err("parameter expected an identifier, such as 'n: i32'.".to_string()); "wild param")]
+    #[test_case("report", "trait A { fn a(&self, (x, y): (u8, u8)); }" => Err("parameter expected an identifier, such as 'n: i32'.".to_string()); "tuple param")]
+    #[test_case("watch", "trait A { fn a(&self, _n: u8) -> u8; }" => Ok(()); "underscore param")]
+    #[test_case("invoke", "trait A { fn call(&self) -> u8; }" => Err("method name 'call' is reserved in invoke interface.".to_string()); "invoke call")]
+    fn test_check(kind: &str, item: &str) -> std::result::Result<(), String> {
+        let trait_context: TraitContext = syn::parse_str(item).unwrap();
+        trait_context.check(kind).map_err(|e| e.to_string())
+    }
+
+    #[test_case("extends = \"Store\"", "trait A: Store + Send { fn a(&self); }" => Ok(()); "supertrait")]
+    #[test_case("extends = \"super::Store, Admin\"", "trait A: super::Store + Admin { fn a(&self); }" => Ok(()); "path")]
+    #[test_case("extends = \"Admin\"", "trait A: Store { fn a(&self); }" => Err("'Admin' in 'extends' must be a supertrait of the interface.".to_string()); "not supertrait")]
+    #[test_case("", "trait A: Store { fn a(&self); }" => Ok(()); "bound only")]
+    fn test_check_extends(attr: &str, item: &str) -> std::result::Result<(), String> {
+        let attributes: InvokeInterfaceAttributes = syn::parse_str(attr).unwrap();
+        let trait_context: TraitContext = syn::parse_str(item).unwrap();
+        trait_context
+            .check_extends(&attributes)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test_case("max_frame_size = 1024" => Ok(()); "max_frame_size")]
+    #[test_case("compress = \"lz4\"" => Err("'compress' is used in invoke interface only.".to_string()); "compress")]
+    #[test_case("compress_threshold = 64" => Err("'compress' is used in invoke interface only.".to_string()); "compress_threshold")]
+    fn test_watch_attributes(attr: &str) -> std::result::Result<(), String> {
+        syn::parse_str::<WatchInterfaceAttributes>(attr)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
 // --
 /*
 #[cfg(test)]
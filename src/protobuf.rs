@@ -0,0 +1,350 @@
+// -- protobuf.rs --
+
+use crate::idl::Interface;
+use crate::utilities::{camel_to_snake, snake_to_camel};
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{GenericArgument, Ident, PathArguments, Type};
+
+// --
+
+// protobuf中可以直接表示的类型：(rust类型, proto类型, 消息中的rust类型)
+const SCALARS: &[(&str, &str, &str)] = &[
+    ("i32", "int32", "i32"),
+    ("i64", "int64", "i64"),
+    ("u32", "uint32", "u32"),
+    ("u64", "uint64", "u64"),
+    ("f32", "float", "f32"),
+    ("f64", "double", "f64"),
+    ("bool", "bool", "bool"),
+    ("String", "string", "String"),
+    ("i8", "int32", "i32"),
+    ("i16", "int32", "i32"),
+    ("u8", "uint32", "u32"),
+    ("u16", "uint32", "u32"),
+    ("isize", "int64", "i64"),
+    ("usize", "uint64", "u64"),
+];
+
+// --
+
+enum Convert {
+    // 类型相同，直接使用
+    Direct,
+    // 整数类型不同，需要转换
+    Cast,
+    // 其他的类型使用bincode编码成bytes
+    Bincode,
+}
+
+struct ProtoField {
+    proto: String,
+    attr: TokenStream2,
+    ty: TokenStream2,
+    convert: Convert,
+}
+
+fn type_args(ty: &Type) -> Option<(String, Vec<&Type>)> {
+    if let Type::Path(p) = ty {
+        let last = p.path.segments.last()?;
+        let args = match &last.arguments {
+            PathArguments::AngleBracketed(a) => a
+                .args
+                .iter()
+                .filter_map(|a| {
+                    if let GenericArgument::Type(t) = a {
+                        Some(t)
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Some((last.ident.to_string(), args))
+    } else {
+        None
+    }
+}
+
+fn scalar(ty: &Type) -> Option<(&'static str, &'static str, bool)> {
+    let (ident, args) = type_args(ty)?;
+    if !args.is_empty() {
+        return None;
+    }
+    SCALARS
+        .iter()
+        .find(|(r, ..)| *r == ident)
+        .map(|(r, p, m)| (*p, *m, r == m))
+}
+
+fn proto_field(ty: &Type) -> ProtoField {
+    let bincode = ProtoField {
+        proto: "bytes".to_string(),
+        attr: quote! { bytes = "vec" },
+        ty: quote! { Vec<u8> },
+        convert: Convert::Bincode,
+    };
+    if let Some((proto, msg, direct)) = scalar(ty) {
+        let attr = Ident::new(proto, proc_macro2::Span::call_site());
+        let msg: TokenStream2 = msg.parse().unwrap();
+        return ProtoField {
+            proto: proto.to_string(),
+            attr: quote! { #attr },
+            ty: msg,
+            convert: if direct {
+                Convert::Direct
+            } else {
+                Convert::Cast
+            },
+        };
+    }
+    match type_args(ty) {
+        Some((ident, args)) if ident == "Vec" && args.len() == 1 => match scalar(args[0]) {
+            // Vec<u8>就是bytes
+            Some(_) if is_u8(args[0]) => ProtoField {
+                proto: "bytes".to_string(),
+                attr: quote! { bytes = "vec" },
+                ty: quote! { Vec<u8> },
+                convert: Convert::Direct,
+            },
+            Some((proto, msg, true)) => {
+                let attr = Ident::new(proto, proc_macro2::Span::call_site());
+                let msg: TokenStream2 = msg.parse().unwrap();
+                ProtoField {
+                    proto: format!("repeated {}", proto),
+                    attr: quote! { #attr, repeated },
+                    ty: quote! { Vec<#msg> },
+                    convert: Convert::Direct,
+                }
+            }
+            _ => bincode,
+        },
+        Some((ident, args)) if ident == "Option" && args.len() == 1 => match scalar(args[0]) {
+            Some((proto, msg, true)) => {
+                let attr = Ident::new(proto, proc_macro2::Span::call_site());
+                let msg: TokenStream2 = msg.parse().unwrap();
+                ProtoField {
+                    proto: format!("optional {}", proto),
+                    attr: quote! { #attr, optional },
+                    ty: quote! { Option<#msg> },
+                    convert: Convert::Direct,
+                }
+            }
+            _ => bincode,
+        },
+        _ => bincode,
+    }
+}
+
+fn is_u8(ty: &Type) -> bool {
+    matches!(type_args(ty), Some((ident, args)) if ident == "u8" && args.is_empty())
+}
+
+fn is_unit(ty: &Type) -> bool {
+    matches!(ty, Type::Tuple(t) if t.elems.is_empty())
+}
+
+fn package(interface: &Interface) -> String {
+    camel_to_snake(&interface.name)
+}
+
+// --
+
+// 每个方法对应一个rpc，参数是{Method}Request，返回值是{Method}Reply的value字段
+pub(crate) fn render_proto(interface: &Interface) -> String {
+    let mut rpcs = String::new();
+    let mut messages = String::new();
+    for m in &interface.methods {
+        let camel = snake_to_camel(&m.name);
+        for line in m.doc.lines() {
+            rpcs.push_str(format!("  // {}", line).trim_end());
+            rpcs.push('\n');
+        }
+        rpcs.push_str(&format!(
+            "  rpc {}({}Request) returns ({}Reply);\n",
+            camel, camel, camel
+        ));
+
+        messages.push_str(&format!("\nmessage {}Request {{\n", camel));
+        for (i, p) in m.params.iter().enumerate() {
+            messages.push_str(&format!(
+                "  {} {} = {};\n",
+                proto_field(&p.ty).proto,
+                p.name,
+                i + 1
+            ));
+        }
+        messages.push_str(&format!("}}\n\nmessage {}Reply {{\n", camel));
+        if !is_unit(&m.output) {
+            messages.push_str(&format!("  {} value = 1;\n", proto_field(&m.output).proto));
+        }
+        messages.push_str("}\n");
+    }
+
+    let mut proto = format!(
+        "// {}.proto, generated by servant-macro from trait {}, do not edit.\n// bytes fields without protobuf equivalent are encoded by bincode.\n\nsyntax = \"proto3\";\n\npackage {};\n\n",
+        interface.name,
+        interface.name,
+        package(interface)
+    );
+    for line in interface.doc.lines() {
+        proto.push_str(format!("// {}", line).trim_end());
+        proto.push('\n');
+    }
+    proto.push_str(&format!(
+        "service {} {{\n{}}}\n{}",
+        interface.name, rpcs, messages
+    ));
+    proto
+}
+
+// 生成protobuf消息，以及把gRPC请求分发给servant的adapter，与servant共享同一个实现类的对象。
+// 解码后的请求通过servant的dispatch调用，与bincode的请求一样经过rate_limit、validate、metrics和tracing
+pub(crate) fn render_grpc(
+    interface: &Interface,
+    trait_ident: &Ident,
+    servant_ident: &Ident,
+    frame_ident: &Ident,
+    request_ident: &Ident,
+    max_frame_size: Option<usize>,
+) -> TokenStream2 {
+    let proto = render_proto(interface);
+    let service_name = format!("{}.{}", package(interface), interface.name);
+    let module_ident = format_ident!("{}_grpc", package(interface));
+    let grpc_ident = format_ident!("{}Grpc", trait_ident);
+
+    let mut messages = Vec::new();
+    let mut arms = Vec::new();
+    for m in interface.methods.iter() {
+        let camel = snake_to_camel(&m.name);
+        let rpc_name = camel.clone();
+        let message_ident = format_ident!("{}Request", camel);
+        let reply_ident = format_ident!("{}Reply", camel);
+        let variant_ident = format_ident!("{}", camel);
+        let output = &m.output;
+
+        let mut request_fields = Vec::new();
+        let mut fields = Vec::new();
+        for (i, p) in m.params.iter().enumerate() {
+            let field = proto_field(&p.ty);
+            let (attr, ty, tag) = (&field.attr, &field.ty, (i + 1).to_string());
+            let name = format_ident!("{}", p.name);
+            let param_ty = &p.ty;
+            request_fields.push(quote! {
+                #[prost(#attr, tag = #tag)]
+                pub #name: #ty,
+            });
+            let value = match field.convert {
+                Convert::Direct => quote! { __servant_msg.#name },
+                Convert::Cast => quote! {
+                    <#param_ty as std::convert::TryFrom<_>>::try_from(__servant_msg.#name)
+                        .map_err(|e| servant::ServantError::from(format!("{}: {}", stringify!(#name), e)))?
+                },
+                Convert::Bincode => quote! {
+                    bincode::deserialize::<#param_ty>(&__servant_msg.#name)
+                        .map_err(|e| servant::ServantError::from(format!("{}: {}", stringify!(#name), e)))?
+                },
+            };
+            // 参数直接解码到请求的field中，不绑定与参数同名的局部变量
+            fields.push(quote! { #name: #value, });
+        }
+        let call = quote! {
+            {
+                let __servant_request = #request_ident::#variant_ident { #(#fields)* };
+                let __servant_reps = #frame_ident::dispatch(
+                    &self.0.entity,
+                    &self.0.limiter,
+                    __servant_ctx,
+                    __servant_request,
+                    __servant_req.len() as u64,
+                );
+                bincode::deserialize::<servant::ServantResult<#output>>(&__servant_reps)
+                    .map_err(|e| servant::ServantError::from(format!("invalid response: {}", e)))??
+            }
+        };
+        let (reply_fields, reply_value) = if is_unit(&m.output) {
+            (quote! {}, quote! { #call; })
+        } else {
+            let field = proto_field(&m.output);
+            let (attr, ty) = (&field.attr, &field.ty);
+            let value = match field.convert {
+                Convert::Direct => quote! { __servant_v },
+                Convert::Cast => quote! { __servant_v as #ty },
+                Convert::Bincode => quote! { bincode::serialize(&__servant_v).unwrap() },
+            };
+            (
+                quote! {
+                    #[prost(#attr, tag = "1")]
+                    pub value: #ty,
+                },
+                quote! {
+                    let __servant_v = #call;
+                    __servant_reply.value = #value;
+                },
+            )
+        };
+        messages.push(quote! {
+            #[derive(Clone, PartialEq, prost::Message)]
+            pub struct #message_ident {
+                #(#request_fields)*
+            }
+            #[derive(Clone, PartialEq, prost::Message)]
+            pub struct #reply_ident {
+                #reply_fields
+            }
+        });
+        arms.push(quote! {
+            #rpc_name => {
+                let __servant_msg = <#module_ident::#message_ident as prost::Message>::decode(__servant_req)
+                    .map_err(|e| servant::ServantError::from(e.to_string()))?;
+                #[allow(unused_mut)]
+                let mut __servant_reply = #module_ident::#reply_ident::default();
+                #reply_value
+                Ok(prost::Message::encode_to_vec(&__servant_reply))
+            }
+        });
+    }
+
+    // 设置了max_frame_size时，先检查请求的大小，再解码
+    let frame_check = match max_frame_size {
+        Some(max) => quote! {
+            if __servant_req.len() > #max {
+                return Err(format!("frame size {} exceeds max_frame_size {}.", __servant_req.len(), #max).into());
+            }
+        },
+        None => TokenStream2::new(),
+    };
+    quote! {
+        pub mod #module_ident {
+            #(#messages)*
+        }
+        pub struct #grpc_ident<S>(std::sync::Arc<#servant_ident<S>>);
+        impl<S> #grpc_ident<S> {
+            pub const PROTO: &'static str = #proto;
+            pub fn new(servant: std::sync::Arc<#servant_ident<S>>) -> Self {
+                Self(servant)
+            }
+        }
+        impl<S> servant::GrpcService for #grpc_ident<S>
+        where
+            S: #trait_ident + 'static,
+        {
+            fn service_name(&self) -> &'static str {
+                #service_name
+            }
+            // 参数和局部变量使用__servant_前缀，与dispatch一样
+            fn call(
+                &self,
+                __servant_ctx: Option<servant::Context>,
+                __servant_method: &str,
+                __servant_req: &[u8],
+            ) -> servant::ServantResult<Vec<u8>> {
+                #frame_check
+                match __servant_method {
+                    #(#arms)*
+                    _ => Err(format!("method '{}' is not found.", __servant_method).into()),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,418 @@
+// -- python.rs --
+
+use crate::idl::Interface;
+use crate::utilities::{snake_to_camel, type_to_string};
+use std::collections::BTreeSet;
+use syn::{GenericArgument, PathArguments, Type};
+
+// --
+
+pub(crate) const RUNTIME_FILE: &str = "servant.py";
+
+// 所有接口共用的代码，按照bincode缺省的配置编码：小端序，整数定长，长度是u64，enum的序号是u32
+pub(crate) const RUNTIME: &str = r#"# servant.py, generated by servant-macro, do not edit.
+
+import struct
+
+_FIXED = {
+    "u8": "<B", "i8": "<b", "u16": "<H", "i16": "<h", "u32": "<I", "i32": "<i",
+    "u64": "<Q", "i64": "<q", "usize": "<Q", "isize": "<q", "f32": "<f", "f64": "<d",
+}
+_CUSTOM = {}
+
+
+class ServantError(Exception):
+    """服务端返回的ServantError，detail是bincode编码的内容"""
+
+    def __init__(self, detail: bytes):
+        super().__init__(detail)
+        self.detail = detail
+
+
+class Transport:
+    """发送bincode编码的请求，返回bincode编码的响应，由使用者实现"""
+
+    def invoke(self, category: str, name, payload: bytes) -> bytes:
+        raise NotImplementedError
+
+
+def register(name: str, encoder, decoder):
+    """注册自定义类型的编码和解码函数：encoder(value, out: bytearray)，decoder(reader: Reader)"""
+    _CUSTOM[name] = (encoder, decoder)
+
+
+class Reader:
+    def __init__(self, data: bytes):
+        self.data = data
+        self.pos = 0
+
+    def take(self, n: int) -> bytes:
+        if self.pos + n > len(self.data):
+            raise ValueError("unexpected end of data")
+        b = self.data[self.pos:self.pos + n]
+        self.pos += n
+        return b
+
+    def rest(self) -> bytes:
+        return self.take(len(self.data) - self.pos)
+
+
+def encode(ty, value, out: bytearray):
+    kind = ty[0]
+    if kind in _FIXED:
+        out += struct.pack(_FIXED[kind], value)
+    elif kind in ("u128", "i128"):
+        out += value.to_bytes(16, "little", signed=kind == "i128")
+    elif kind == "bool":
+        out.append(1 if value else 0)
+    elif kind == "str":
+        b = value.encode("utf-8")
+        out += struct.pack("<Q", len(b))
+        out += b
+    elif kind == "char":
+        out += value.encode("utf-8")
+    elif kind == "unit":
+        pass
+    elif kind == "option":
+        if value is None:
+            out.append(0)
+        else:
+            out.append(1)
+            encode(ty[1], value, out)
+    elif kind == "seq":
+        out += struct.pack("<Q", len(value))
+        for v in value:
+            encode(ty[1], v, out)
+    elif kind == "array":
+        for v in value:
+            encode(ty[1], v, out)
+    elif kind == "map":
+        out += struct.pack("<Q", len(value))
+        for k, v in value.items():
+            encode(ty[1], k, out)
+            encode(ty[2], v, out)
+    elif kind == "tuple":
+        for t, v in zip(ty[1:], value):
+            encode(t, v, out)
+    elif kind == "custom":
+        _CUSTOM[ty[1]][0](value, out)
+    else:
+        raise TypeError("unsupported type: %r" % (ty,))
+
+
+def decode(ty, r: Reader):
+    kind = ty[0]
+    if kind in _FIXED:
+        fmt = _FIXED[kind]
+        return struct.unpack(fmt, r.take(struct.calcsize(fmt)))[0]
+    elif kind in ("u128", "i128"):
+        return int.from_bytes(r.take(16), "little", signed=kind == "i128")
+    elif kind == "bool":
+        return r.take(1)[0] != 0
+    elif kind == "str":
+        n = decode(("u64",), r)
+        return r.take(n).decode("utf-8")
+    elif kind == "char":
+        first = r.data[r.pos]
+        n = 1 if first < 0x80 else 2 if first < 0xE0 else 3 if first < 0xF0 else 4
+        return r.take(n).decode("utf-8")
+    elif kind == "unit":
+        return None
+    elif kind == "option":
+        return decode(ty[1], r) if r.take(1)[0] else None
+    elif kind == "seq":
+        n = decode(("u64",), r)
+        return [decode(ty[1], r) for _ in range(n)]
+    elif kind == "array":
+        return [decode(ty[1], r) for _ in range(ty[2])]
+    elif kind == "map":
+        n = decode(("u64",), r)
+        return dict((decode(ty[1], r), decode(ty[2], r)) for _ in range(n))
+    elif kind == "tuple":
+        return tuple(decode(t, r) for t in ty[1:])
+    elif kind == "custom":
+        return _CUSTOM[ty[1]][1](r)
+    raise TypeError("unsupported type: %r" % (ty,))
+
+
+def decode_result(ty, data: bytes):
+    """解码ServantResult<T>：Ok是0，Err是1"""
+    r = Reader(data)
+    if decode(("u32",), r) == 0:
+        return decode(ty, r)
+    raise ServantError(r.rest())
+"#;
+
+// Python的关键字，以及生成的代码中使用的名字，作为参数或者方法的名字时在后面加上下划线
+const PY_RESERVED: &str = "False None True and as assert async await break class continue def del \
+    elif else except finally for from global if import in is lambda nonlocal not or pass raise \
+    return try while with yield out data payload encode decode_result struct bytes bytearray";
+
+// --
+
+// 只有invoke和watch接口有返回值，客户端可以调用
+pub(crate) fn supported(kind: &str) -> bool {
+    kind == "invoke" || kind == "watch"
+}
+
+pub(crate) fn render(interface: &Interface) -> String {
+    let mut customs = BTreeSet::new();
+    let name = &interface.name;
+    let request = interface.request_name();
+    let response = format!("{}Response", name);
+    let proxy = format!("{}Proxy", name);
+
+    let mut requests = Vec::new();
+    let mut responses = Vec::new();
+    let mut methods = Vec::new();
+    for (i, m) in interface.methods.iter().enumerate() {
+        let method = py_ident(&m.name);
+        let args: Vec<_> = m.params.iter().map(|p| py_ident(&p.name)).collect();
+        let params: Vec<_> = m
+            .params
+            .iter()
+            .zip(&args)
+            .map(|(p, arg)| format!("{}: {}", arg, py_hint(&p.ty)))
+            .collect();
+        let encodes: String = m
+            .params
+            .iter()
+            .zip(&args)
+            .map(|(p, arg)| {
+                format!(
+                    "        encode({}, {}, out)\n",
+                    py_schema(&p.ty, &mut customs),
+                    arg
+                )
+            })
+            .collect();
+        let output = py_hint(&m.output);
+
+        requests.push(format!(
+            "    @staticmethod\n    def {}({}) -> bytes:\n        \"\"\"{}::{}\"\"\"\n        out = bytearray()\n        encode((\"u32\",), {}, out)\n{}        return bytes(out)\n",
+            method,
+            params.join(", "),
+            request,
+            snake_to_camel(&m.name),
+            i,
+            encodes
+        ));
+        responses.push(format!(
+            "    @staticmethod\n    def {}(data: bytes) -> {}:\n        return decode_result({}, data)\n",
+            method,
+            output,
+            py_schema(&m.output, &mut customs)
+        ));
+        methods.push(format!(
+            "    def {}(self{}) -> {}:\n{}        payload = FRAME + {}.{}({})\n        return {}.{}(self.transport.invoke(self.category, self.name, payload))\n",
+            method,
+            params.iter().map(|p| format!(", {}", p)).collect::<String>(),
+            output,
+            render_doc(&m.doc, "        "),
+            request,
+            method,
+            args.join(", "),
+            response,
+            method
+        ));
+    }
+    // invoke的请求是XxxFrame::Call，序号是0；watch的请求没有frame，也没有对象的名字
+    let (frame, constructor) = if interface.kind == "watch" {
+        (
+            "b\"\"",
+            "    def __init__(self, transport: Transport):\n        self.transport = transport\n        self.name = None\n",
+        )
+    } else {
+        (
+            "struct.pack(\"<I\", 0)",
+            "    def __init__(self, transport: Transport, name: str):\n        self.transport = transport\n        self.name = name\n",
+        )
+    };
+
+    let mut py = format!(
+        "# {}.py, generated by servant-macro from trait {}, do not edit.\n\nimport struct\nfrom typing import Any, Dict, List, Optional, Tuple\n\nfrom servant import Transport, decode_result, encode\n\nFRAME = {}\n",
+        name, name, frame
+    );
+    if !customs.is_empty() {
+        py.push_str(&format!(
+            "\n# 自定义类型，需要使用servant.register注册编码和解码函数：{}\n",
+            customs.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+    py.push_str(&format!("\n\nclass {}:\n{}", request, requests.join("\n")));
+    py.push_str(&format!(
+        "\n\nclass {}:\n{}",
+        response,
+        responses.join("\n")
+    ));
+    py.push_str(&format!(
+        "\n\nclass {}:\n{}    category = \"{}\"\n\n{}\n{}",
+        proxy,
+        render_doc(&interface.doc, "    "),
+        name,
+        constructor,
+        methods.join("\n")
+    ));
+    py
+}
+
+// 去掉原始标识符的`r#`，与Python的关键字等冲突时在后面加上下划线
+fn py_ident(name: &str) -> String {
+    let name = name.trim_start_matches("r#");
+    if PY_RESERVED.split_whitespace().any(|k| k == name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn render_doc(doc: &str, indent: &str) -> String {
+    if doc.is_empty() {
+        return String::new();
+    }
+    let doc = doc.replace('\\', "\\\\").replace("\"\"\"", "\\\"\\\"\\\"");
+    let lines: Vec<_> = doc.lines().collect();
+    if lines.len() == 1 {
+        format!("{}\"\"\"{}\"\"\"\n", indent, lines[0])
+    } else {
+        let body: Vec<_> = lines
+            .iter()
+            .map(|l| format!("{}{}", indent, l).trim_end().to_string())
+            .collect();
+        format!("{}\"\"\"\n{}\n{}\"\"\"\n", indent, body.join("\n"), indent)
+    }
+}
+
+fn type_args(ty: &Type) -> Option<(String, Vec<&Type>)> {
+    if let Type::Path(p) = ty {
+        let last = p.path.segments.last().unwrap();
+        let args = match &last.arguments {
+            PathArguments::AngleBracketed(a) => a
+                .args
+                .iter()
+                .filter_map(|a| {
+                    if let GenericArgument::Type(t) = a {
+                        Some(t)
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Some((last.ident.to_string(), args))
+    } else {
+        None
+    }
+}
+
+// serde的数据模型对应的schema，servant.py按照schema编码和解码
+fn py_schema(ty: &Type, customs: &mut BTreeSet<String>) -> String {
+    match ty {
+        Type::Path(_) => {
+            let (ident, args) = type_args(ty).unwrap();
+            match (ident.as_str(), args.as_slice()) {
+                (
+                    "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+                    | "u128" | "usize" | "f32" | "f64" | "bool" | "char",
+                    _,
+                ) => format!("(\"{}\",)", ident),
+                ("String" | "str", _) => "(\"str\",)".to_string(),
+                ("Option", [t]) => format!("(\"option\", {})", py_schema(t, customs)),
+                ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [t]) => {
+                    format!("(\"seq\", {})", py_schema(t, customs))
+                }
+                ("HashMap" | "BTreeMap", [k, v]) => format!(
+                    "(\"map\", {}, {})",
+                    py_schema(k, customs),
+                    py_schema(v, customs)
+                ),
+                ("Box" | "Rc" | "Arc", [t]) => py_schema(t, customs),
+                _ => {
+                    let ty = type_to_string(&quote! {#ty}.to_string());
+                    customs.insert(ty.clone());
+                    format!("(\"custom\", \"{}\")", ty)
+                }
+            }
+        }
+        Type::Reference(r) => py_schema(&r.elem, customs),
+        Type::Paren(p) => py_schema(&p.elem, customs),
+        Type::Group(g) => py_schema(&g.elem, customs),
+        Type::Tuple(t) if t.elems.is_empty() => "(\"unit\",)".to_string(),
+        Type::Tuple(t) => {
+            let elems: Vec<_> = t.elems.iter().map(|t| py_schema(t, customs)).collect();
+            format!("(\"tuple\", {})", elems.join(", "))
+        }
+        Type::Slice(s) => format!("(\"seq\", {})", py_schema(&s.elem, customs)),
+        Type::Array(a) => {
+            let len = &a.len;
+            format!(
+                "(\"array\", {}, {})",
+                py_schema(&a.elem, customs),
+                type_to_string(&quote! {#len}.to_string())
+            )
+        }
+        _ => {
+            let ty = type_to_string(&quote! {#ty}.to_string());
+            customs.insert(ty.clone());
+            format!("(\"custom\", \"{}\")", ty)
+        }
+    }
+}
+
+fn py_hint(ty: &Type) -> String {
+    match ty {
+        Type::Path(_) => {
+            let (ident, args) = type_args(ty).unwrap();
+            match (ident.as_str(), args.as_slice()) {
+                (
+                    "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+                    | "u128" | "usize",
+                    _,
+                ) => "int".to_string(),
+                ("f32" | "f64", _) => "float".to_string(),
+                ("bool", _) => "bool".to_string(),
+                ("String" | "str" | "char", _) => "str".to_string(),
+                ("Option", [t]) => format!("Optional[{}]", py_hint(t)),
+                ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [t]) => {
+                    format!("List[{}]", py_hint(t))
+                }
+                ("HashMap" | "BTreeMap", [k, v]) => {
+                    format!("Dict[{}, {}]", py_hint(k), py_hint(v))
+                }
+                ("Box" | "Rc" | "Arc", [t]) => py_hint(t),
+                _ => "Any".to_string(),
+            }
+        }
+        Type::Reference(r) => py_hint(&r.elem),
+        Type::Paren(p) => py_hint(&p.elem),
+        Type::Group(g) => py_hint(&g.elem),
+        Type::Tuple(t) if t.elems.is_empty() => "None".to_string(),
+        Type::Tuple(t) => {
+            let elems: Vec<_> = t.elems.iter().map(py_hint).collect();
+            format!("Tuple[{}]", elems.join(", "))
+        }
+        Type::Slice(s) => format!("List[{}]", py_hint(&s.elem)),
+        Type::Array(a) => format!("List[{}]", py_hint(&a.elem)),
+        _ => "Any".to_string(),
+    }
+}
+
+// --
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("n" => "n")]
+    #[test_case("from" => "from_")]
+    #[test_case("lambda" => "lambda_")]
+    #[test_case("r#in" => "in_")]
+    #[test_case("out" => "out_")]
+    #[test_case("encode" => "encode_")]
+    #[test_case("r#type" => "type")]
+    fn test_py_ident(name: &str) -> String {
+        py_ident(name)
+    }
+}
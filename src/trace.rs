@@ -0,0 +1,113 @@
+// -- trace.rs --
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{FnArg, Ident, Pat, TraitItemMethod};
+
+// --
+
+const REDACTED: &str = "<redacted>";
+
+// --
+
+// 方法调用的span，名字是{category}.{method}，记录每个参数的值；redact的参数只记录REDACTED。
+// 生成的字段使用servant.前缀，不会与同名的参数冲突
+pub(crate) fn render_span(
+    trait_ident: &Ident,
+    method: &TraitItemMethod,
+    redact_vec: &[bool],
+    trace_id: Option<&TokenStream2>,
+) -> TokenStream2 {
+    let fn_ident = &method.sig.ident;
+    let name = format!("{}.{}", trait_ident, fn_ident);
+    let mut fields = vec![
+        quote! { servant.category = stringify!(#trait_ident) },
+        quote! { servant.method = stringify!(#fn_ident) },
+    ];
+    if let Some(trace_id) = trace_id {
+        fields.push(quote! { servant.trace_id = #trace_id });
+    }
+    let params = method.sig.inputs.iter().filter_map(|i| {
+        if let FnArg::Typed(pat) = i {
+            Some(pat)
+        } else {
+            None
+        }
+    });
+    for (pat, redact) in params.zip(redact_vec.iter()) {
+        if let Pat::Ident(p) = &*pat.pat {
+            let ident = &p.ident;
+            fields.push(if *redact {
+                quote! { #ident = #REDACTED }
+            } else {
+                quote! { #ident = ?#ident }
+            });
+        }
+    }
+    quote! {
+        tracing::info_span!(#name, #(#fields),*)
+    }
+}
+
+// 进入span，直到当前的作用域结束；没有tracing feature时什么也不做。
+// 局部变量使用__servant_前缀，不会遮住同名的参数
+pub(crate) fn render_enter(span: &TokenStream2) -> TokenStream2 {
+    if cfg!(feature = "tracing") {
+        quote! {
+            let __servant_span = #span;
+            let __servant_enter = __servant_span.enter();
+        }
+    } else {
+        TokenStream2::new()
+    }
+}
+
+// 调用结束的event，reps是ServantResult
+pub(crate) fn render_event(reps: &TokenStream2) -> TokenStream2 {
+    if cfg!(feature = "tracing") {
+        quote! {
+            match &#reps {
+                Ok(_) => tracing::debug!("call completed"),
+                Err(e) => tracing::warn!(error = %e, "call failed"),
+            }
+        }
+    } else {
+        TokenStream2::new()
+    }
+}
+
+// async方法的body在span中执行，body的值是ServantResult，结束时输出event
+pub(crate) fn render_instrument(span: &TokenStream2, body: &TokenStream2) -> TokenStream2 {
+    if !cfg!(feature = "tracing") {
+        return body.clone();
+    }
+    let event = render_event(&quote! { __servant_reps });
+    quote! {
+        let __servant_span = #span;
+        let __servant_reps =
+            tracing::Instrument::instrument(async { #body }, __servant_span.clone()).await;
+        __servant_span.in_scope(|| #event);
+        __servant_reps
+    }
+}
+
+// report和notify接口的错误不会返回给调用者，没有设置on_error时，tracing feature输出error event，否则丢弃
+pub(crate) fn render_error(trait_ident: &Ident, name: Option<&TokenStream2>) -> TokenStream2 {
+    if !cfg!(feature = "tracing") {
+        return quote! {
+            let _ = e;
+        };
+    }
+    match name {
+        Some(name) => quote! {
+            tracing::error!(
+                servant.category = stringify!(#trait_ident),
+                servant.name = %#name,
+                error = %e,
+                "servant failed"
+            );
+        },
+        None => quote! {
+            tracing::error!(servant.category = stringify!(#trait_ident), error = %e, "servant failed");
+        },
+    }
+}
@@ -0,0 +1,439 @@
+// -- typescript.rs --
+
+use crate::idl::Interface;
+use crate::utilities::{snake_to_camel, type_to_string};
+use std::collections::BTreeSet;
+use syn::{GenericArgument, PathArguments, Type};
+
+// --
+
+pub(crate) const RUNTIME_FILE: &str = "servant.ts";
+
+// 所有接口共用的代码：Transport由使用者实现，按照http feature的约定发送请求：
+// 成功时状态码是200，body是JSON编码的返回值；失败时body是{"error": "..."}
+pub(crate) const RUNTIME: &str = r#"// servant.ts, generated by servant-macro, do not edit.
+
+export class ServantError extends Error {
+  constructor(readonly status: number, readonly detail: unknown) {
+    super(typeof detail === "string" ? detail : JSON.stringify(detail));
+  }
+}
+
+// 发送POST请求，path是/{category}/{object_name}/{method}，body是JSON编码的命名参数
+export interface Transport {
+  request(path: string, body: string): Promise<{ status: number; body: string }>;
+}
+
+const BIGINT = "@servant.bigint:";
+
+// bigint编码成JSON中的整数
+export function encode(params: object): string {
+  return JSON.stringify(params, (_, v) => (typeof v === "bigint" ? BIGINT + v.toString() : v))
+    .replace(new RegExp(`"${BIGINT}(-?\\d+)"`, "g"), "$1");
+}
+
+// 超过Number.MAX_SAFE_INTEGER的整数解码成bigint，不丢失精度
+export function decode(text: string): unknown {
+  const marked = text.replace(/"(?:[^"\\]|\\.)*"|-?\d+(?:\.\d+)?(?:[eE][+-]?\d+)?/g, (t) =>
+    /^-?\d+$/.test(t) && !Number.isSafeInteger(Number(t)) ? `"${BIGINT}${t}"` : t,
+  );
+  return JSON.parse(marked, (_, v) =>
+    typeof v === "string" && v.startsWith(BIGINT) ? BigInt(v.slice(BIGINT.length)) : v,
+  );
+}
+
+export function bigint(v: unknown): bigint {
+  return BigInt(v as number | bigint);
+}
+
+export async function call(
+  transport: Transport,
+  category: string,
+  name: string | null,
+  method: string,
+  params: object,
+): Promise<unknown> {
+  // watch接口没有对象的名字，gateway不检查这一段
+  const path = `/${category}/${encodeURIComponent(name ?? "_")}/${method}`;
+  const reps = await transport.request(path, encode(params));
+  const body = reps.body ? decode(reps.body) : null;
+  if (reps.status === 200) {
+    return body;
+  }
+  const error = body !== null && typeof body === "object" && "error" in body ? (body as { error: unknown }).error : body;
+  throw new ServantError(reps.status, error);
+}
+"#;
+
+// JavaScript的保留字，以及生成的代码中使用的名字，作为参数或者方法的名字时在后面加上下划线
+const TS_RESERVED: &str = "break case catch class const continue debugger default delete do else \
+    enum export extends false finally for function if import in instanceof new null return super switch \
+    this throw true try typeof var void while with implements interface let package private \
+    protected public static yield await arguments eval constructor transport name call bigint v";
+
+// --
+
+// 只有invoke和watch接口有返回值，客户端可以通过JSON调用
+pub(crate) fn supported(kind: &str) -> bool {
+    kind == "invoke" || kind == "watch"
+}
+
+pub(crate) fn render(interface: &Interface) -> String {
+    let mut customs = BTreeSet::new();
+    let name = &interface.name;
+    let proxy = format!("{}Proxy", name);
+
+    let mut variants = String::new();
+    let mut responses = String::new();
+    let mut methods = Vec::new();
+    for m in &interface.methods {
+        let fields: Vec<_> = m
+            .params
+            .iter()
+            .map(|p| format!("{}: {}", ts_key(&p.name), ts_type(&p.ty, &mut customs)))
+            .collect();
+        let params: Vec<_> = m
+            .params
+            .iter()
+            .map(|p| format!("{}: {}", ts_ident(&p.name), ts_type(&p.ty, &mut customs)))
+            .collect();
+        // JSON中使用参数原来的名字，名字加了下划线时不能使用简写
+        let args: Vec<_> = m
+            .params
+            .iter()
+            .map(|p| {
+                let (key, ident) = (ts_key(&p.name), ts_ident(&p.name));
+                if key == ident {
+                    ident
+                } else {
+                    format!("{}: {}", key, ident)
+                }
+            })
+            .collect();
+        let output = ts_type(&m.output, &mut customs);
+
+        variants.push_str(&format!(
+            "\n  | {{ {}: {} }}",
+            snake_to_camel(&m.name),
+            braced(&fields.join("; "))
+        ));
+        responses.push_str(&format!("  {}: {};\n", m.name, output));
+        // JSON中的整数解码成number，返回值中的bigint需要转换
+        let reps = match ts_convert(&m.output) {
+            Some(f) => format!("({})(v)", f),
+            None => "v".to_string(),
+        };
+        methods.push(format!(
+            "{}  async {}({}): Promise<{}> {{\n    const v = await call(this.transport, {}.category, this.name, \"{}\", {});\n    return {} as {};\n  }}\n",
+            render_doc(&m.doc, "  "),
+            ts_ident(&m.name),
+            params.join(", "),
+            output,
+            proxy,
+            m.name,
+            braced(&args.join(", ")),
+            reps,
+            output
+        ));
+    }
+    // watch接口没有对象的名字，每个Server只有一个watch接口
+    let constructor = if interface.kind == "watch" {
+        "  private readonly name = null;\n\n  constructor(private readonly transport: Transport) {}\n"
+    } else {
+        "  constructor(private readonly transport: Transport, private readonly name: string) {}\n"
+    };
+
+    let mut ts = format!(
+        "// {}.ts, generated by servant-macro from trait {}, do not edit.\n\nimport {{ Transport, bigint, call }} from \"./servant\";\n\n",
+        name, name
+    );
+    for c in &customs {
+        ts.push_str(&format!(
+            "// 自定义类型，接口定义中没有它的结构\nexport type {} = unknown;\n\n",
+            c
+        ));
+    }
+    ts.push_str(&format!(
+        "export type {} ={};\n\n",
+        interface.request_name(),
+        variants
+    ));
+    ts.push_str(&format!(
+        "export interface {}Response {{\n{}}}\n\n",
+        name, responses
+    ));
+    ts.push_str(&render_doc(&interface.doc, ""));
+    ts.push_str(&format!(
+        "export class {} {{\n  static readonly category = \"{}\";\n\n{}\n{}}}\n",
+        proxy,
+        name,
+        constructor,
+        methods.join("\n")
+    ));
+    ts
+}
+
+fn ts_key(name: &str) -> String {
+    name.trim_start_matches("r#").to_string()
+}
+
+fn ts_ident(name: &str) -> String {
+    let name = ts_key(name);
+    if TS_RESERVED.split_whitespace().any(|k| k == name) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+fn braced(fields: &str) -> String {
+    if fields.is_empty() {
+        "{}".to_string()
+    } else {
+        format!("{{ {} }}", fields)
+    }
+}
+
+fn render_doc(doc: &str, indent: &str) -> String {
+    if doc.is_empty() {
+        return String::new();
+    }
+    let lines: Vec<_> = doc
+        .replace("*/", "* /")
+        .lines()
+        .map(|l| format!("{} * {}", indent, l).trim_end().to_string())
+        .collect();
+    format!("{}/**\n{}\n{} */\n", indent, lines.join("\n"), indent)
+}
+
+// 超过Number.MAX_SAFE_INTEGER的整数在TypeScript中使用bigint
+const BIGINT_TYPES: &[&str] = &["i64", "i128", "isize", "u64", "u128", "usize"];
+
+// serde_json编码的类型：64位以上的整数是bigint，其他整数和浮点数是number，Option是null，unit是null
+fn ts_type(ty: &Type, customs: &mut BTreeSet<String>) -> String {
+    match ty {
+        Type::Path(p) => {
+            let last = p.path.segments.last().unwrap();
+            let args: Vec<_> = match &last.arguments {
+                PathArguments::AngleBracketed(a) => a
+                    .args
+                    .iter()
+                    .filter_map(|a| {
+                        if let GenericArgument::Type(t) = a {
+                            Some(ts_type(t, customs))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            match (last.ident.to_string().as_str(), args.as_slice()) {
+                (ident, _) if BIGINT_TYPES.contains(&ident) => "bigint".to_string(),
+                ("i8" | "i16" | "i32" | "u8" | "u16" | "u32" | "f32" | "f64", _) => {
+                    "number".to_string()
+                }
+                ("bool", _) => "boolean".to_string(),
+                ("String" | "str" | "char", _) => "string".to_string(),
+                ("Option", [t]) => format!("{} | null", t),
+                ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [t]) => format!("{}[]", wrap(t)),
+                ("HashMap" | "BTreeMap", [_, v]) => format!("Record<string, {}>", v),
+                ("Box" | "Rc" | "Arc", [t]) => t.clone(),
+                ("Result", [t, e]) => format!("{{ Ok: {} }} | {{ Err: {} }}", t, e),
+                (ident, _) => {
+                    customs.insert(ident.to_string());
+                    ident.to_string()
+                }
+            }
+        }
+        Type::Reference(r) => ts_type(&r.elem, customs),
+        Type::Paren(p) => ts_type(&p.elem, customs),
+        Type::Group(g) => ts_type(&g.elem, customs),
+        Type::Tuple(t) if t.elems.is_empty() => "null".to_string(),
+        Type::Tuple(t) => {
+            let elems: Vec<_> = t.elems.iter().map(|t| ts_type(t, customs)).collect();
+            format!("[{}]", elems.join(", "))
+        }
+        Type::Slice(s) => format!("{}[]", wrap(&ts_type(&s.elem, customs))),
+        Type::Array(a) => format!("{}[]", wrap(&ts_type(&a.elem, customs))),
+        _ => {
+            let ty = type_to_string(&quote! {#ty}.to_string());
+            format!("unknown /* {} */", ty)
+        }
+    }
+}
+
+// 返回值中有bigint时，生成把解码后的JSON转换成这个类型的函数；没有bigint时返回None
+fn ts_convert(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(p) => {
+            let last = p.path.segments.last().unwrap();
+            let ident = last.ident.to_string();
+            if BIGINT_TYPES.contains(&ident.as_str()) {
+                return Some("bigint".to_string());
+            }
+            let args: Vec<_> = match &last.arguments {
+                PathArguments::AngleBracketed(a) => a
+                    .args
+                    .iter()
+                    .filter_map(|a| match a {
+                        GenericArgument::Type(t) => Some(t),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            match (ident.as_str(), args.as_slice()) {
+                ("Option", [t]) => ts_convert(t)
+                    .map(|f| format!("(x: unknown) => (x === null ? null : ({})(x))", f)),
+                ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [t]) => ts_convert(t)
+                    .map(|f| format!("(x: unknown) => (x as unknown[]).map((y) => ({})(y))", f)),
+                ("HashMap" | "BTreeMap", [_, v]) => ts_convert(v).map(|f| {
+                    format!(
+                        "(x: unknown) => Object.fromEntries(Object.entries(x as object).map(([k, y]) => [k, ({})(y)]))",
+                        f
+                    )
+                }),
+                ("Box" | "Rc" | "Arc", [t]) => ts_convert(t),
+                ("Result", [t, _]) => ts_convert(t).map(|f| {
+                    format!(
+                        "(x: unknown) => (\"Ok\" in (x as object) ? {{ Ok: ({})((x as {{ Ok: unknown }}).Ok) }} : x)",
+                        f
+                    )
+                }),
+                _ => None,
+            }
+        }
+        Type::Reference(r) => ts_convert(&r.elem),
+        Type::Paren(p) => ts_convert(&p.elem),
+        Type::Group(g) => ts_convert(&g.elem),
+        Type::Tuple(t) => {
+            let elems: Vec<_> = t.elems.iter().map(ts_convert).collect();
+            if elems.iter().all(Option::is_none) {
+                return None;
+            }
+            let elems: Vec<_> = elems
+                .iter()
+                .enumerate()
+                .map(|(i, f)| match f {
+                    Some(f) => format!("({})(t[{}])", f, i),
+                    None => format!("t[{}]", i),
+                })
+                .collect();
+            Some(format!(
+                "(x: unknown) => {{ const t = x as unknown[]; return [{}]; }}",
+                elems.join(", ")
+            ))
+        }
+        Type::Slice(s) => ts_convert(&s.elem)
+            .map(|f| format!("(x: unknown) => (x as unknown[]).map((y) => ({})(y))", f)),
+        Type::Array(a) => ts_convert(&a.elem)
+            .map(|f| format!("(x: unknown) => (x as unknown[]).map((y) => ({})(y))", f)),
+        _ => None,
+    }
+}
+
+// 联合类型作为数组的元素时需要加括号
+fn wrap(ty: &str) -> String {
+    if ty.contains('|') {
+        format!("({})", ty)
+    } else {
+        ty.to_string()
+    }
+}
+
+// --
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::TraitContext;
+    use test_case::test_case;
+
+    #[test]
+    fn test_render_typescript() {
+        let trait_context: TraitContext = syn::parse_quote! {
+            pub trait Hello {
+                /// say hello.
+                fn hello(&self, n: i32, names: Vec<Option<String>>) -> Option<String>;
+                fn login(&mut self, cookie: UserCookie) -> (bool, u64);
+            }
+        };
+        let ts = render(&trait_context.interface("invoke"));
+        assert!(ts.contains("import { Transport, bigint, call } from \"./servant\";"));
+        assert!(ts.contains("export type UserCookie = unknown;"));
+        assert!(ts.contains("| { Hello: { n: number; names: (string | null)[] } }"));
+        assert!(ts.contains("hello: string | null;"));
+        assert!(ts.contains("login: [boolean, bigint];"));
+        assert!(ts.contains("  /**\n   * say hello.\n   */\n"));
+        assert!(ts.contains(
+            "async hello(n: number, names: (string | null)[]): Promise<string | null> {"
+        ));
+        assert!(ts.contains(
+            "const v = await call(this.transport, HelloProxy.category, this.name, \"hello\", { n, names });\n    return v as string | null;"
+        ));
+        assert!(ts.contains(
+            "return ((x: unknown) => { const t = x as unknown[]; return [t[0], (bigint)(t[1])]; })(v) as [boolean, bigint];"
+        ));
+    }
+
+    // http feature的约定：200时body是返回值，其他状态码时body是{"error": ...}
+    #[test]
+    fn test_runtime_contract() {
+        assert!(RUNTIME.contains(
+            "const path = `/${category}/${encodeURIComponent(name ?? \"_\")}/${method}`;"
+        ));
+        assert!(RUNTIME.contains("if (reps.status === 200) {\n    return body;\n  }"));
+        assert!(RUNTIME.contains("\"error\" in body ? (body as { error: unknown }).error : body;"));
+        assert!(RUNTIME.contains("throw new ServantError(reps.status, error);"));
+    }
+
+    #[test_case("i32" => "number".to_string(); "i32")]
+    #[test_case("u64" => "bigint".to_string(); "u64")]
+    #[test_case("i128" => "bigint".to_string(); "i128")]
+    #[test_case("Option<u64>" => "bigint | null".to_string(); "option")]
+    #[test_case("Vec<Option<i64>>" => "(bigint | null)[]".to_string(); "vec")]
+    #[test_case("HashMap<String, u128>" => "Record<string, bigint>".to_string(); "map")]
+    fn test_ts_type(ty: &str) -> String {
+        ts_type(&syn::parse_str(ty).unwrap(), &mut BTreeSet::new())
+    }
+
+    #[test_case("String" => None; "string")]
+    #[test_case("(bool, i32)" => None; "tuple without bigint")]
+    #[test_case("u64" => Some("bigint".to_string()); "u64")]
+    #[test_case("Option<u64>" => Some("(x: unknown) => (x === null ? null : (bigint)(x))".to_string()); "option")]
+    #[test_case("Vec<i64>" => Some("(x: unknown) => (x as unknown[]).map((y) => (bigint)(y))".to_string()); "vec")]
+    #[test_case("Box<[u128; 2]>" => Some("(x: unknown) => (x as unknown[]).map((y) => (bigint)(y))".to_string()); "array")]
+    fn test_ts_convert(ty: &str) -> Option<String> {
+        ts_convert(&syn::parse_str(ty).unwrap())
+    }
+
+    #[test_case("n" => "n")]
+    #[test_case("delete" => "delete_")]
+    #[test_case("new" => "new_")]
+    #[test_case("r#in" => "in_")]
+    #[test_case("constructor" => "constructor_")]
+    #[test_case("v" => "v_")]
+    #[test_case("r#type" => "type")]
+    fn test_ts_ident(name: &str) -> String {
+        ts_ident(name)
+    }
+
+    #[test]
+    fn test_render_typescript_reserved() {
+        let trait_context: TraitContext = syn::parse_quote! {
+            pub trait Store {
+                fn delete(&mut self, r#in: String, default: i32, n: i32) -> bool;
+            }
+        };
+        let ts = render(&trait_context.interface("invoke"));
+        assert!(ts.contains("| { Delete: { in: string; default: number; n: number } }"));
+        assert!(ts.contains("delete: boolean;"));
+        assert!(ts.contains(
+            "async delete_(in_: string, default_: number, n: number): Promise<boolean> {"
+        ));
+        assert!(ts.contains(
+            "call(this.transport, StoreProxy.category, this.name, \"delete\", { in: in_, default: default_, n });"
+        ));
+    }
+}
@@ -19,6 +19,47 @@ pub fn snake_to_camel(ident_str: &str) -> String {
     camel_ty
 }
 
+pub fn camel_to_snake(ident_str: &str) -> String {
+    let mut snake_ty = String::new();
+    let chars: Vec<_> = ident_str.chars().collect();
+
+    // 连续的大写字母是一个单词，例如"HTTPServer" => "http_server"
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev_lower = !chars[i - 1].is_uppercase() && chars[i - 1] != '_';
+            let next_lower = matches!(chars.get(i + 1), Some(n) if n.is_lowercase());
+            if prev_lower || (chars[i - 1].is_uppercase() && next_lower) {
+                snake_ty.push('_');
+            }
+        }
+        snake_ty.extend(c.to_lowercase());
+    }
+
+    snake_ty
+}
+
+// quote!生成的类型字符串中有多余的空格，例如"Vec < String >" => "Vec<String>"
+pub fn type_to_string(type_str: &str) -> String {
+    let mut ty = String::new();
+    let mut chars = type_str.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == ' ' {
+            let last_compact = ty.is_empty() || ty.ends_with(|c| "<(&[:".contains(c));
+            let next_compact = match chars.peek() {
+                Some(c) => "<>(),;:[]".contains(*c) && !ty.ends_with(&[',', ';'][..]),
+                None => true,
+            };
+            if last_compact || next_compact {
+                continue;
+            }
+        }
+        ty.push(c);
+    }
+
+    ty
+}
+
 #[allow(unused)]
 fn snake_to_camel2(ident_str: &str) -> String {
     let mut camel_ty = String::new();
@@ -40,6 +81,19 @@ fn snake_to_camel2(ident_str: &str) -> String {
     camel_ty
 }
 
+// rate_limit的值，例如"100/s"，返回(次数, 毫秒)
+pub fn parse_rate_limit(rate: &str) -> Option<(u64, u64)> {
+    let mut it = rate.splitn(2, '/');
+    let count = it.next()?.trim().parse::<u64>().ok().filter(|c| *c > 0)?;
+    let period_ms = match it.next()?.trim() {
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return None,
+    };
+    Some((count, period_ms))
+}
+
 // --
 
 #[cfg(test)]
@@ -60,4 +114,33 @@ mod tests {
         // snake_to_camel(ident_str)
         snake_to_camel2(ident_str)
     }
+
+    #[test_case("AbcDef" => "abc_def".to_string(); "basic")]
+    #[test_case("Abc" => "abc".to_string(); "single")]
+    #[test_case("abcDef" => "abc_def".to_string(); "lower first")]
+    #[test_case("HTTPServer" => "http_server".to_string(); "acronym first")]
+    #[test_case("StoreHTTP" => "store_http".to_string(); "acronym last")]
+    #[test_case("IOError" => "io_error".to_string(); "short acronym")]
+    #[test_case("Store2Admin" => "store2_admin".to_string(); "digit")]
+    fn test_camel_to_snake(ident_str: &str) -> String {
+        camel_to_snake(ident_str)
+    }
+
+    #[test_case("Vec < String >" => "Vec<String>".to_string(); "generic")]
+    #[test_case("& 'static str" => "&'static str".to_string(); "reference")]
+    #[test_case("std :: collections :: HashMap < u8 , Vec < u8 > >" => "std::collections::HashMap<u8, Vec<u8>>".to_string(); "path")]
+    #[test_case("(i32 , [u8 ; 4])" => "(i32, [u8; 4])".to_string(); "tuple")]
+    fn test_type_to_string(type_str: &str) -> String {
+        type_to_string(type_str)
+    }
+
+    #[test_case("100/s" => Some((100, 1_000)); "second")]
+    #[test_case("5 / m" => Some((5, 60_000)); "minute")]
+    #[test_case("1/h" => Some((1, 3_600_000)); "hour")]
+    #[test_case("0/s" => None; "zero")]
+    #[test_case("100/d" => None; "unit")]
+    #[test_case("100" => None; "no unit")]
+    fn test_parse_rate_limit(rate: &str) -> Option<(u64, u64)> {
+        parse_rate_limit(rate)
+    }
 }
@@ -10,23 +10,31 @@ mod utilities;
     proxy = "HelloProxy2",
     servant = "HelloServant2",
     callback = false,
-    persistency = true
+    persistency = true,
+    retry = 2,
+    retry_backoff_ms = 10
 )]
+/// say hello to servant.
 pub trait Hello {
+    /// return n as string.
+    #[servant(idempotent, cache_ttl_ms = 500)]
     fn hello(&self, n: i32) -> String;
     fn bye(&self);
 }
 
 // --
 
+#[derive(serde::Serialize)]
 struct HelloEntity;
 impl HelloEntity {
     const N_VALUE: i32 = 88;
+    const N_PANIC: i32 = -1;
     const NAME_VALUE: &'static str = "he1";
 }
 impl Hello for HelloEntity {
     fn hello(&self, _ctx: Option<servant::Context>, n: i32) -> String {
         assert!(_ctx.is_none());
+        assert_ne!(n, Self::N_PANIC);
         return n.to_string();
     }
     fn bye(&self, _ctx: Option<servant::Context>) {
@@ -90,12 +98,288 @@ fn test_hello_servant() {
     );
 }
 
+#[test]
+fn test_hello_servant_panic() {
+    use servant::Servant;
+
+    let he = HelloServant2::new(HelloEntity::NAME_VALUE, HelloEntity);
+    let serve = |n| {
+        let req = bincode::serialize(&HelloFrame::Call(HelloRequest::Hello { n })).unwrap();
+        let reps = he.serve(None, req);
+        bincode::deserialize::<servant::ServantResult<String>>(&reps).unwrap()
+    };
+    assert!(serve(HelloEntity::N_PANIC).is_err());
+    assert_eq!(
+        serve(HelloEntity::N_VALUE).unwrap(),
+        HelloEntity::N_VALUE.to_string()
+    );
+}
+
+#[test]
+fn test_hello_servant_cancelled() {
+    use servant::Servant;
+
+    let he = HelloServant2::new(HelloEntity::NAME_VALUE, HelloEntity);
+    let ctx = servant::Context::default();
+    ctx.clone().cancel();
+    assert!(ctx.is_cancelled());
+
+    // 已经取消的请求不调用实现类，HelloEntity中ctx不是None时会panic
+    let frame = HelloFrame::Call(HelloRequest::Hello {
+        n: HelloEntity::N_VALUE,
+    });
+    let reps = he.serve(Some(ctx.clone()), bincode::serialize(&frame).unwrap());
+    let r = bincode::deserialize::<servant::ServantResult<String>>(&reps).unwrap();
+    assert!(r.unwrap_err().to_string().contains("cancelled"));
+
+    // 取消的响应按照任何返回类型都解码为Err
+    assert!(bincode::deserialize::<servant::ServantResult<()>>(&reps)
+        .unwrap()
+        .is_err());
+    assert!(bincode::deserialize::<servant::ServantResult<u64>>(&reps)
+        .unwrap()
+        .is_err());
+    assert!(
+        bincode::deserialize::<servant::ServantResult<Vec<(i8, String)>>>(&reps)
+            .unwrap()
+            .is_err()
+    );
+
+    // 批量调用中的每个请求都返回Err
+    let frame = HelloFrame::Batch(vec![
+        HelloRequest::Bye {},
+        HelloRequest::Hello {
+            n: HelloEntity::N_VALUE,
+        },
+    ]);
+    let reps = he.serve(Some(ctx), bincode::serialize(&frame).unwrap());
+    let reps: Vec<Vec<u8>> = bincode::deserialize(&reps).unwrap();
+    assert!(bincode::deserialize::<servant::ServantResult<()>>(&reps[0])
+        .unwrap()
+        .is_err());
+    assert!(
+        bincode::deserialize::<servant::ServantResult<String>>(&reps[1])
+            .unwrap()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_hello_servant_batch() {
+    use servant::Servant;
+
+    let he = HelloServant2::new(HelloEntity::NAME_VALUE, HelloEntity);
+    let frame = HelloFrame::Batch(vec![
+        HelloRequest::Hello {
+            n: HelloEntity::N_VALUE,
+        },
+        HelloRequest::Bye {},
+        HelloRequest::Hello {
+            n: HelloEntity::N_PANIC,
+        },
+    ]);
+    let reps = he.serve(None, bincode::serialize(&frame).unwrap());
+    let reps: Vec<Vec<u8>> = bincode::deserialize(&reps).unwrap();
+    assert_eq!(reps.len(), 3);
+    assert_eq!(
+        bincode::deserialize::<servant::ServantResult<String>>(&reps[0])
+            .unwrap()
+            .unwrap(),
+        HelloEntity::N_VALUE.to_string()
+    );
+    assert!(bincode::deserialize::<servant::ServantResult<()>>(&reps[1])
+        .unwrap()
+        .is_ok());
+    assert!(
+        bincode::deserialize::<servant::ServantResult<String>>(&reps[2])
+            .unwrap()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_hello_proxy_batch() {
+    use servant::Servant;
+
+    let he = HelloServant2::new(HelloEntity::NAME_VALUE, HelloEntity);
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Invoke { req, .. } => Ok(he.serve(None, req)),
+        _ => unreachable!(),
+    });
+    let proxy = HelloProxy2::new(servant::Context::default(), HelloEntity::NAME_VALUE, &t);
+    let mut batch = proxy.batch();
+    batch
+        .hello(HelloEntity::N_VALUE)
+        .bye()
+        .hello(HelloEntity::N_PANIC);
+    let reps = futures::executor::block_on(batch.call()).unwrap();
+    assert_eq!(reps.len(), 3);
+    assert!(matches!(&reps[0], HelloResponse::Hello(Ok(s)) if s == "88"));
+    assert!(matches!(&reps[1], HelloResponse::Bye(Ok(()))));
+    assert!(matches!(&reps[2], HelloResponse::Hello(Err(_))));
+}
+
+#[test]
+fn test_hello_proxy_batch_invalid_response() {
+    // 响应中的结果不能解码时，返回ServantError
+    let t = servant::Terminal::loopback(|_| {
+        Ok(bincode::serialize(&vec![vec![1u8], vec![0u8, 0, 0, 0]]).unwrap())
+    });
+    let proxy = HelloProxy2::new(servant::Context::default(), HelloEntity::NAME_VALUE, &t);
+    let mut batch = proxy.batch();
+    batch.hello(HelloEntity::N_VALUE).bye();
+    let reps = futures::executor::block_on(batch.call()).unwrap();
+    assert!(
+        matches!(&reps[0], HelloResponse::Hello(Err(e)) if e.to_string().contains("invalid response"))
+    );
+    assert!(matches!(&reps[1], HelloResponse::Bye(Ok(()))));
+
+    // 响应的个数与调用的个数不同
+    let t = servant::Terminal::loopback(|_| Ok(bincode::serialize(&vec![vec![0u8; 4]]).unwrap()));
+    let proxy = HelloProxy2::new(servant::Context::default(), HelloEntity::NAME_VALUE, &t);
+    let mut batch = proxy.batch();
+    batch.bye().bye();
+    assert!(futures::executor::block_on(batch.call()).is_err());
+}
+
+#[test]
+fn test_hello_proxy_retry() {
+    use servant::Servant;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // 传输错误时重试，retry = 2，第三次成功
+    let he = HelloServant2::new(HelloEntity::NAME_VALUE, HelloEntity);
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Invoke { req, .. } => match c.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => Err(servant::ServantError::transport("reset")),
+            _ => Ok(he.serve(None, req)),
+        },
+        _ => unreachable!(),
+    });
+    let mut proxy = HelloProxy2::new(servant::Context::default(), HelloEntity::NAME_VALUE, &t);
+    let r = futures::executor::block_on(proxy.hello(HelloEntity::N_VALUE));
+    assert_eq!(r.unwrap(), "88");
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+
+    // 重试的次数用完，返回传输错误
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let t = servant::Terminal::loopback(move |_| {
+        c.fetch_add(1, Ordering::SeqCst);
+        Err(servant::ServantError::transport("reset"))
+    });
+    let mut proxy = HelloProxy2::new(servant::Context::default(), HelloEntity::NAME_VALUE, &t);
+    let r = futures::executor::block_on(proxy.hello(HelloEntity::N_VALUE));
+    assert!(r.unwrap_err().is_transport());
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+
+    // 其他错误和没有标记idempotent的方法都不重试
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let t = servant::Terminal::loopback(move |call| {
+        c.fetch_add(1, Ordering::SeqCst);
+        match call {
+            servant::Loopback::Invoke { .. } => Err("not found".into()),
+            _ => unreachable!(),
+        }
+    });
+    let mut proxy = HelloProxy2::new(servant::Context::default(), HelloEntity::NAME_VALUE, &t);
+    assert!(futures::executor::block_on(proxy.hello(HelloEntity::N_VALUE)).is_err());
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    let c = count.clone();
+    let t = servant::Terminal::loopback(move |_| {
+        c.fetch_add(1, Ordering::SeqCst);
+        Err(servant::ServantError::transport("reset"))
+    });
+    let mut proxy = HelloProxy2::new(servant::Context::default(), HelloEntity::NAME_VALUE, &t);
+    assert!(futures::executor::block_on(proxy.bye()).is_err());
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_hello_proxy_cache() {
+    use servant::Servant;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let he = HelloServant2::new(HelloEntity::NAME_VALUE, HelloEntity);
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Invoke { req, .. } => {
+            c.fetch_add(1, Ordering::SeqCst);
+            Ok(he.serve(None, req))
+        }
+        _ => unreachable!(),
+    });
+    let mut proxy = HelloProxy2::new(servant::Context::default(), HelloEntity::NAME_VALUE, &t);
+    let mut hello = |n| futures::executor::block_on(proxy.hello(n)).unwrap();
+
+    // 相同的参数使用缓存的结果，不同的参数调用servant
+    assert_eq!(hello(HelloEntity::N_VALUE), "88");
+    assert_eq!(hello(HelloEntity::N_VALUE), "88");
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    assert_eq!(hello(1), "1");
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+
+    // 超过cache_ttl_ms后，缓存的结果过期
+    std::thread::sleep(std::time::Duration::from_millis(600));
+    assert_eq!(hello(HelloEntity::N_VALUE), "88");
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+
+    // 清除缓存后，调用servant
+    proxy.invalidate_hello();
+    futures::executor::block_on(proxy.hello(HelloEntity::N_VALUE)).unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 4);
+    proxy.invalidate_cache();
+    futures::executor::block_on(proxy.hello(HelloEntity::N_VALUE)).unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 5);
+
+    // Context改变后，不使用以前的缓存
+    proxy.context_mut();
+    futures::executor::block_on(proxy.hello(HelloEntity::N_VALUE)).unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 6);
+
+    // 缓存满时，淘汰最久没有使用的结果
+    for n in 1000..1300 {
+        futures::executor::block_on(proxy.hello(n)).unwrap();
+    }
+    assert_eq!(count.load(Ordering::SeqCst), 306);
+    futures::executor::block_on(proxy.hello(1299)).unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 306);
+    futures::executor::block_on(proxy.hello(HelloEntity::N_VALUE)).unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 307);
+}
+
 #[test]
 fn test_hello_proxy() {
     show_type!(HelloProxy2);
+    show_type!(HelloProxy2Batch);
     assert_eq!(HelloProxy2::category(), "Hello");
 }
 
+#[test]
+fn test_hello_descriptor() {
+    let d = HelloServant2::<HelloEntity>::DESCRIPTOR;
+    assert_eq!(d.kind, servant::InterfaceKind::Invoke);
+    assert_eq!(d.name, "Hello");
+    assert_eq!(d.doc, "say hello to servant.");
+    assert_eq!(d.methods.len(), 2);
+    assert_eq!(d.methods[0].name, "hello");
+    assert_eq!(d.methods[0].params[0].name, "n");
+    assert_eq!(d.methods[0].params[0].ty, "i32");
+    assert_eq!(d.methods[0].output, "String");
+    assert_eq!(d.methods[0].doc, "return n as string.");
+    assert_eq!(d.methods[1].name, "bye");
+    assert!(d.methods[1].params.is_empty());
+    assert_eq!(d.methods[1].output, "()");
+
+    assert_eq!(HelloProxy2::DESCRIPTOR.name, d.name);
+}
+
 #[test]
 fn build_01_invoke() {
     let t = trybuild::TestCases::new();
@@ -13,6 +13,11 @@ pub trait Foo {
     fn f3(&mut self, s: String);
 }
 
+#[servant::report_interface(delivery = "at_least_once", outbox_capacity = 16)]
+pub trait Audit {
+    fn log(&mut self, msg: String);
+}
+
 // --
 
 struct FooEntity;
@@ -35,6 +40,14 @@ impl Foo for FooEntity {
     }
 }
 
+struct AuditEntity(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+impl Audit for AuditEntity {
+    fn log(&mut self, msg: String) {
+        dbg!(msg);
+        self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
 // --
 
 #[test]
@@ -100,12 +113,274 @@ fn test_foo_officer() {
     );
 }
 
+#[test]
+fn test_foo_officer_serve() {
+    use servant::ReportServant;
+
+    let officer = FooOfficer::new(FooEntity::NAME_VALUE, FooEntity);
+    let officer = &officer;
+    let req = vec![
+        FooRequest::F1 {
+            count: FooEntity::F1_COUNT,
+        },
+        FooRequest::F2 {},
+        FooRequest::F3 {
+            s: FooEntity::F3_S.to_owned(),
+        },
+    ];
+    req.into_iter().for_each(|r| {
+        let frame = FooFrame::Report(r);
+        officer.serve(bincode::serialize(&frame).unwrap())
+    });
+    assert_eq!(officer.name(), FooEntity::NAME_VALUE);
+}
+
+#[test]
+fn test_foo_officer_on_error() {
+    use servant::ReportServant;
+
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let e = errors.clone();
+    let officer = FooOfficer::new(FooEntity::NAME_VALUE, FooEntity)
+        .on_error(move |err: &servant::ServantError| e.lock().unwrap().push(err.to_string()));
+    let report = |count| bincode::serialize(&FooFrame::Report(FooRequest::F1 { count })).unwrap();
+    officer.serve(report(0));
+    officer.serve(report(FooEntity::F1_COUNT));
+    // 不能解码的请求也交给on_error
+    officer.serve(vec![0xff]);
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].starts_with("servant panicked"));
+}
+
+// 记录同时在方法中的调用数，&self的方法等待其他的调用进入，最多等待1秒
+#[derive(Default)]
+struct ProbeEntity {
+    active: std::sync::atomic::AtomicUsize,
+    max: std::sync::atomic::AtomicUsize,
+}
+impl ProbeEntity {
+    fn enter(&self, wait: bool) {
+        use std::sync::atomic::Ordering;
+
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max.fetch_max(active, Ordering::SeqCst);
+        let start = std::time::Instant::now();
+        while wait
+            && self.max.load(Ordering::SeqCst) < 2
+            && start.elapsed() < std::time::Duration::from_secs(1)
+        {
+            std::thread::yield_now();
+        }
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+impl Foo for ProbeEntity {
+    fn f1(&self, _count: i32) {
+        self.enter(true);
+    }
+    fn f2(&self) {
+        self.enter(true);
+    }
+    fn f3(&mut self, _s: String) {
+        self.enter(false);
+    }
+}
+
+#[test]
+fn test_foo_officer_concurrent() {
+    use servant::ReportServant;
+
+    // 两个线程同时调用&self的方法，在读锁下并发执行
+    let officer = FooOfficer::new(FooEntity::NAME_VALUE, ProbeEntity::default());
+    let f1 = bincode::serialize(&FooFrame::Report(FooRequest::F1 { count: 1 })).unwrap();
+    let f2 = bincode::serialize(&FooFrame::Report(FooRequest::F2 {})).unwrap();
+    std::thread::scope(|s| {
+        s.spawn(|| officer.serve(f1.clone()));
+        s.spawn(|| officer.serve(f2.clone()));
+    });
+    let max = |officer: &FooOfficer<ProbeEntity>| {
+        let entity = officer.entity.read().unwrap();
+        entity.max.swap(0, std::sync::atomic::Ordering::SeqCst)
+    };
+    assert_eq!(max(&officer), 2);
+
+    // &mut self的方法在写锁下独占执行
+    let f3 = bincode::serialize(&FooFrame::Report(FooRequest::F3 { s: "s".into() })).unwrap();
+    std::thread::scope(|s| {
+        s.spawn(|| officer.serve(f3.clone()));
+        s.spawn(|| officer.serve(f3.clone()));
+    });
+    assert_eq!(max(&officer), 1);
+}
+
 #[test]
 fn test_foo_staff() {
     show_type!(FooStaff);
+    show_type!(FooStaffBatch);
     assert_eq!(FooStaff::category(), "Foo");
 }
 
+#[test]
+fn test_audit_officer_dedup() {
+    use servant::ReportServant;
+
+    let count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let officer = AuditOfficer::new("audit1", AuditEntity(count.clone()));
+    let envelope = |origin, seq| {
+        let request = AuditRequest::Log {
+            msg: format!("{}-{}", origin, seq),
+        };
+        bincode::serialize(&AuditEnvelope {
+            origin,
+            seq,
+            frame: AuditFrame::Report(request),
+        })
+        .unwrap()
+    };
+    officer.serve(envelope(1, 0));
+    officer.serve(envelope(1, 1));
+    officer.serve(envelope(1, 1));
+    officer.serve(envelope(1, 0));
+    officer.serve(envelope(2, 0));
+    assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_audit_officer_batch() {
+    use servant::ReportServant;
+
+    let count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let officer = AuditOfficer::new("audit2", AuditEntity(count.clone()));
+    let requests = (0..5)
+        .map(|i| AuditRequest::Log { msg: i.to_string() })
+        .collect();
+    let envelope = AuditEnvelope {
+        origin: 1,
+        seq: 0,
+        frame: AuditFrame::Batch(requests),
+    };
+    officer.serve(bincode::serialize(&envelope).unwrap());
+    officer.serve(bincode::serialize(&envelope).unwrap());
+    assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 5);
+}
+
+#[test]
+fn test_audit_officer_window() {
+    use servant::ReportServant;
+
+    let count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let officer = AuditOfficer::new("audit3", AuditEntity(count.clone()));
+    let envelope = |seq: u64| {
+        let request = AuditRequest::Log {
+            msg: seq.to_string(),
+        };
+        let envelope = AuditEnvelope {
+            origin: 1,
+            seq,
+            frame: AuditFrame::Report(request),
+        };
+        bincode::serialize(&envelope).unwrap()
+    };
+    // 窗口之内乱序到达的报告都可以接收
+    for seq in [1, 0, 3, 2, 3, 0, 200, 150, 150, 72] {
+        officer.serve(envelope(seq));
+    }
+    assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 6);
+}
+
+#[test]
+fn test_audit_staff_redelivery() {
+    use servant::ReportServant;
+
+    let count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let officer = std::sync::Arc::new(AuditOfficer::new("audit4", AuditEntity(count.clone())));
+    // 每次发送的结果：lost是报告没有到达officer，no_ack是officer收到了报告，但是确认没有到达staff
+    let plan = std::sync::Arc::new(std::sync::Mutex::new(vec!["ok", "ok", "no_ack", "lost"]));
+    let (o, p) = (officer.clone(), plan.clone());
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Report { req, acked, .. } => {
+            assert!(acked);
+            match p.lock().unwrap().pop().unwrap_or("ok") {
+                "lost" => Err(servant::ServantError::transport("lost")),
+                "no_ack" => {
+                    o.serve(req);
+                    Err(servant::ServantError::transport("no ack"))
+                }
+                _ => {
+                    o.serve(req);
+                    Ok(Vec::new())
+                }
+            }
+        }
+        _ => unreachable!(),
+    });
+    let outbox = servant::Outbox::memory(16);
+    let mut staff = AuditStaff::with_outbox("audit4", &t, outbox.clone());
+    futures::executor::block_on(async {
+        // 报告放入outbox之后就返回Ok，发送失败的报告保留在outbox中
+        staff.log("a".to_string()).await.unwrap();
+        assert_eq!(outbox.len(), 1);
+        assert!(staff.flush().await.is_err());
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 1);
+        // 重新发送的报告被officer丢弃
+        staff.flush().await.unwrap();
+        assert!(outbox.is_empty());
+        staff.log("b".to_string()).await.unwrap();
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // 使用同一个outbox的staff，从之前的序号继续
+        let mut staff = AuditStaff::with_outbox("audit4", &t, outbox.clone());
+        staff.log("c".to_string()).await.unwrap();
+    });
+    assert_eq!(outbox.last_seq(), Some(2));
+    assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_audit_staff_outbox_full() {
+    use servant::ReportServant;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    let count = std::sync::Arc::new(AtomicUsize::new(0));
+    let officer = std::sync::Arc::new(AuditOfficer::new("audit5", AuditEntity(count.clone())));
+    let connected = std::sync::Arc::new(AtomicBool::new(false));
+    let (o, c) = (officer.clone(), connected.clone());
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Report { req, .. } if c.load(Ordering::SeqCst) => {
+            o.serve(req);
+            Ok(Vec::new())
+        }
+        _ => Err(servant::ServantError::transport("not connected")),
+    });
+    let outbox = servant::Outbox::memory(16);
+    let mut staff = AuditStaff::with_outbox("audit5", &t, outbox.clone());
+    futures::executor::block_on(async {
+        for i in 0..16 {
+            staff.log(i.to_string()).await.unwrap();
+        }
+        // outbox满时返回错误，不使用新的序号
+        assert!(staff.log("x".to_string()).await.is_err());
+        assert_eq!(outbox.last_seq(), Some(15));
+        assert!(staff.flush().await.is_err());
+
+        // 连接恢复之后flush，按照序号发送所有的报告
+        connected.store(true, Ordering::SeqCst);
+        staff.flush().await.unwrap();
+        assert!(outbox.is_empty());
+        assert_eq!(count.load(Ordering::SeqCst), 16);
+        staff.log("y".to_string()).await.unwrap();
+    });
+    assert_eq!(outbox.last_seq(), Some(16));
+    assert_eq!(count.load(Ordering::SeqCst), 17);
+}
+
+#[test]
+fn test_audit_staff() {
+    show_type!(AuditStaff);
+    assert_eq!(AuditStaff::category(), "Audit");
+}
+
 #[test]
 fn build_02_report() {
     let t = trybuild::TestCases::new();
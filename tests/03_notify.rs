@@ -6,13 +6,19 @@ mod utilities;
 // --
 
 // these code are been tested.
-#[servant::notify_interface]
+#[servant::notify_interface(stream = true)]
 pub trait Bar {
     fn f1(&self, count: i32);
     fn f2(&self, msg: String);
     fn f3(&mut self, count: usize, f: f64, b: Option<bool>, s: Vec<String>);
 }
 
+// 参数与生成的代码中的名字相同
+#[servant::notify_interface]
+pub trait Baz {
+    fn rename(&mut self, entity: String, req: usize, reps: usize);
+}
+
 // --
 
 struct BarEntity;
@@ -41,6 +47,13 @@ impl Bar for BarEntity {
     }
 }
 
+struct BazEntity(std::sync::Arc<std::sync::Mutex<(String, usize)>>);
+impl Baz for BazEntity {
+    fn rename(&mut self, entity: String, req: usize, reps: usize) {
+        *self.0.lock().unwrap() = (entity, req + reps);
+    }
+}
+
 // --
 
 #[test]
@@ -74,11 +87,141 @@ fn test_bar_receiver() {
     );
 }
 
+#[test]
+fn test_bar_receiver_on_error() {
+    use servant::NotifyServant;
+
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let e = errors.clone();
+    let mut receiver = BarReceiver::new(BarEntity)
+        .on_error(move |err: &servant::ServantError| e.lock().unwrap().push(err.to_string()));
+    let notify = |count| bincode::serialize(&BarRequest::F1 { count }).unwrap();
+    receiver.serve(notify(0));
+    receiver.serve(notify(BarEntity::F1_COUNT));
+    // 不能解码的通知也交给on_error
+    receiver.serve(vec![0xff]);
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].starts_with("servant panicked"));
+}
+
+#[test]
+fn test_baz_receiver_shadowing_parameters() {
+    use servant::NotifyServant;
+
+    let state = std::sync::Arc::new(std::sync::Mutex::new((String::new(), 0)));
+    let mut receiver = BazReceiver::new(BazEntity(state.clone()));
+    let req = BazRequest::Rename {
+        entity: "baz1".to_string(),
+        req: 1,
+        reps: 2,
+    };
+    receiver.serve(bincode::serialize(&req).unwrap());
+    assert_eq!(*state.lock().unwrap(), ("baz1".to_string(), 3));
+}
+
 #[test]
 fn test_pusher_report_notifier() {
     show_type!(BarNotifier);
 }
 
+#[test]
+fn test_bar_notifier_recipient() {
+    let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let s = sent.clone();
+    let ar = servant::AdapterRegister::with_sender(move |to, topic, msg| {
+        s.lock().unwrap().push((to, topic.to_string(), msg));
+    });
+    let notifier = BarNotifier::new(ar);
+    futures::executor::block_on(async {
+        notifier.f1(BarEntity::F1_COUNT).await.unwrap();
+        notifier
+            .to(7)
+            .f2(BarEntity::F2_MSG.to_owned())
+            .await
+            .unwrap();
+        notifier.to_group("admin").f1(1).await.unwrap();
+    });
+
+    let sent = sent.lock().unwrap();
+    let recipients: Vec<_> = sent
+        .iter()
+        .map(|(to, topic, _)| (to.clone(), topic.as_str()))
+        .collect();
+    assert_eq!(
+        recipients,
+        vec![
+            (servant::Recipient::All, "f1"),
+            (servant::Recipient::Client(7), "f2"),
+            (servant::Recipient::Group("admin".to_string()), "f1"),
+        ]
+    );
+    let req: BarRequest = bincode::deserialize(&sent[1].2).unwrap();
+    assert!(matches!(req, BarRequest::F2 { msg } if msg == BarEntity::F2_MSG));
+}
+
+// 发送失败时，notifier的方法返回错误
+#[test]
+fn test_bar_notifier_not_connected() {
+    let notifier = BarNotifier::new(servant::AdapterRegister::default());
+    let reps = futures::executor::block_on(notifier.f1(1));
+    assert!(reps.unwrap_err().is_transport());
+}
+
+#[test]
+fn test_bar_subscription() {
+    show_type!(BarSubscription);
+
+    let req = BarRequest::F2 {
+        msg: BarEntity::F2_MSG.to_owned(),
+    };
+    assert_eq!(req.topic(), "f2");
+    assert_eq!(BarRequest::F1 { count: 0 }.topic(), "f1");
+}
+
+#[test]
+fn test_bar_subscribe() {
+    let subscribed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let s = subscribed.clone();
+    let t = servant::Terminal::loopback(move |call| {
+        if let servant::Loopback::Subscribe { category, topics } = call {
+            let topics = topics.join(",");
+            s.lock().unwrap().push(format!("{}: {}", category, topics));
+        }
+        Ok(Vec::new())
+    });
+    futures::executor::block_on(async {
+        let subscription = BarSubscription::new(&t).f1().f3();
+        subscription.subscribe().await.unwrap();
+        BarSubscription::new(&t).all().subscribe().await.unwrap();
+    });
+    assert_eq!(
+        *subscribed.lock().unwrap(),
+        vec!["Bar: f1,f3", "Bar: f1,f2,f3"]
+    );
+}
+
+#[test]
+fn test_bar_stream() {
+    use futures::StreamExt;
+    use servant::NotifyServant;
+
+    let (mut sender, mut stream) = BarStream::channel();
+    // 不能解码的通知不发送到stream
+    sender.serve(vec![0xff]);
+    let req = BarRequest::F1 {
+        count: BarEntity::F1_COUNT,
+    };
+    sender.serve(bincode::serialize(&req).unwrap());
+    drop(sender);
+
+    match futures::executor::block_on(stream.next()) {
+        Some(BarEvent::F1 { count }) => assert_eq!(count, BarEntity::F1_COUNT),
+        _ => panic!("BarEvent::F1 expected"),
+    }
+    assert!(futures::executor::block_on(stream.next()).is_none());
+}
+
 #[test]
 fn build_02_report() {
     let t = trybuild::TestCases::new();
@@ -92,4 +235,5 @@ fn main() {
     show_type!(BarNotifier);
     show_type!(BarEntity);
     show_type!(BarReceiver<BarEntity>);
+    show_type!(BarSubscription);
 }
@@ -0,0 +1,138 @@
+// -- 04_compose.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface]
+pub trait Counter {
+    fn get(&self) -> usize;
+    fn add(&mut self, n: usize) -> usize;
+}
+
+#[servant::invoke_interface]
+pub trait Admin {
+    fn reset(&mut self);
+    // 参数与生成的代码中的名字相同
+    fn rename(
+        &mut self,
+        entity: String,
+        limiter: usize,
+        ctx: usize,
+        req: usize,
+        request_bytes: usize,
+    ) -> String;
+}
+
+#[servant::compose(Counter, Admin)]
+pub struct CounterEntity {
+    count: usize,
+}
+
+// --
+
+impl CounterEntity {
+    const NAME_VALUE: &'static str = "counter1";
+    const ADD_N: usize = 5;
+}
+impl Counter for CounterEntity {
+    fn get(&self, _ctx: Option<servant::Context>) -> usize {
+        self.count
+    }
+    fn add(&mut self, _ctx: Option<servant::Context>, n: usize) -> usize {
+        self.count += n;
+        self.count
+    }
+}
+impl Admin for CounterEntity {
+    fn reset(&mut self, _ctx: Option<servant::Context>) {
+        self.count = 0;
+    }
+    fn rename(
+        &mut self,
+        _ctx: Option<servant::Context>,
+        entity: String,
+        limiter: usize,
+        ctx: usize,
+        req: usize,
+        request_bytes: usize,
+    ) -> String {
+        self.count = limiter + ctx + req + request_bytes;
+        entity
+    }
+}
+
+// --
+
+fn serve<T: serde::de::DeserializeOwned>(
+    servant: &CounterEntityServant,
+    category: &str,
+    req: Vec<u8>,
+) -> servant::ServantResult<T> {
+    use servant::CompositeServant;
+
+    let reps = servant.serve(category, None, req);
+    bincode::deserialize(&reps).unwrap()
+}
+
+#[test]
+fn test_counter_composite_servant() {
+    show_type!(CounterEntityServant);
+    assert_eq!(CounterEntityServant::categories(), vec!["Counter", "Admin"]);
+
+    let s = CounterEntityServant::new(CounterEntity::NAME_VALUE, CounterEntity { count: 0 });
+    let add = bincode::serialize(&CounterFrame::Call(CounterRequest::Add {
+        n: CounterEntity::ADD_N,
+    }))
+    .unwrap();
+    assert_eq!(
+        serve::<usize>(&s, "Counter", add.clone()).unwrap(),
+        CounterEntity::ADD_N
+    );
+    assert_eq!(
+        serve::<usize>(&s, "Counter", add).unwrap(),
+        CounterEntity::ADD_N * 2
+    );
+
+    let reset = bincode::serialize(&AdminFrame::Call(AdminRequest::Reset {})).unwrap();
+    assert!(serve::<()>(&s, "Admin", reset.clone()).is_ok());
+    let get = bincode::serialize(&CounterFrame::Call(CounterRequest::Get {})).unwrap();
+    assert_eq!(serve::<usize>(&s, "Counter", get).unwrap(), 0);
+
+    assert!(serve::<()>(&s, "Unknown", reset).is_err());
+}
+
+#[test]
+fn test_counter_shadowing_parameters() {
+    let s = CounterEntityServant::new(CounterEntity::NAME_VALUE, CounterEntity { count: 0 });
+    let rename = bincode::serialize(&AdminFrame::Call(AdminRequest::Rename {
+        entity: "counter2".to_string(),
+        limiter: 1,
+        ctx: 2,
+        req: 3,
+        request_bytes: 4,
+    }))
+    .unwrap();
+    assert_eq!(
+        serve::<String>(&s, "Admin", rename).unwrap(),
+        "counter2".to_string()
+    );
+    let get = bincode::serialize(&CounterFrame::Call(CounterRequest::Get {})).unwrap();
+    assert_eq!(serve::<usize>(&s, "Counter", get).unwrap(), 1 + 2 + 3 + 4);
+}
+
+#[test]
+fn build_04_compose() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/04_compose.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(CounterRequest);
+    show_type!(AdminRequest);
+    show_type!(CounterEntityServant);
+}
@@ -0,0 +1,142 @@
+// -- 05_inherit.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface]
+pub trait Store {
+    fn get(&self, key: String) -> Option<String>;
+    fn set(&mut self, key: String, value: String);
+}
+
+#[servant::invoke_interface(extends = "Store")]
+pub trait StoreAdmin: Store + Send {
+    fn clear(&mut self) -> usize;
+}
+
+// --
+
+struct StoreEntity(std::collections::HashMap<String, String>);
+impl StoreEntity {
+    const NAME_VALUE: &'static str = "store1";
+    const KEY_VALUE: &'static str = "k1";
+    const VALUE_VALUE: &'static str = "v1";
+}
+impl Store for StoreEntity {
+    fn get(&self, _ctx: Option<servant::Context>, key: String) -> Option<String> {
+        self.0.get(&key).cloned()
+    }
+    fn set(&mut self, _ctx: Option<servant::Context>, key: String, value: String) {
+        self.0.insert(key, value);
+    }
+}
+impl StoreAdmin for StoreEntity {
+    fn clear(&mut self, _ctx: Option<servant::Context>) -> usize {
+        let n = self.0.len();
+        self.0.clear();
+        n
+    }
+}
+
+// --
+
+fn serve<T: serde::de::DeserializeOwned>(
+    servant: &StoreAdminServant<StoreEntity>,
+    category: &str,
+    req: Vec<u8>,
+) -> servant::ServantResult<T> {
+    use servant::CompositeServant;
+
+    let reps = servant.serve(category, None, req);
+    bincode::deserialize(&reps).unwrap()
+}
+
+#[test]
+fn test_store_admin_servant() {
+    use servant::CompositeServant;
+
+    show_type!(StoreAdminServant<StoreEntity>);
+    let s = StoreAdminServant::new(
+        StoreEntity::NAME_VALUE,
+        StoreEntity(std::collections::HashMap::new()),
+    );
+    assert_eq!(s.categories(), vec!["StoreAdmin", "Store"]);
+
+    let set = bincode::serialize(&StoreFrame::Call(StoreRequest::Set {
+        key: StoreEntity::KEY_VALUE.to_string(),
+        value: StoreEntity::VALUE_VALUE.to_string(),
+    }))
+    .unwrap();
+    assert!(serve::<()>(&s, "Store", set).is_ok());
+    let get = bincode::serialize(&StoreFrame::Call(StoreRequest::Get {
+        key: StoreEntity::KEY_VALUE.to_string(),
+    }))
+    .unwrap();
+    assert_eq!(
+        serve::<Option<String>>(&s, "Store", get.clone()).unwrap(),
+        Some(StoreEntity::VALUE_VALUE.to_string())
+    );
+
+    let clear = bincode::serialize(&StoreAdminFrame::Call(StoreAdminRequest::Clear {})).unwrap();
+    assert_eq!(serve::<usize>(&s, "StoreAdmin", clear.clone()).unwrap(), 1);
+    assert_eq!(serve::<Option<String>>(&s, "Store", get).unwrap(), None);
+
+    assert!(serve::<usize>(&s, "Unknown", clear).is_err());
+}
+
+#[test]
+fn test_store_admin_proxy() {
+    show_type!(StoreAdminProxy);
+    assert_eq!(StoreAdminProxy::category(), "StoreAdmin");
+
+    let _: fn(&StoreAdminProxy) -> StoreProxy = StoreAdminProxy::as_store;
+}
+
+#[test]
+fn test_store_admin_proxy_deref() {
+    use servant::CompositeServant;
+
+    let s = StoreAdminServant::new(
+        StoreEntity::NAME_VALUE,
+        StoreEntity(std::collections::HashMap::new()),
+    );
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Invoke { oid, req, .. } => {
+            let oid = oid.unwrap();
+            assert_eq!(oid.name(), StoreEntity::NAME_VALUE);
+            Ok(s.serve(oid.category(), None, req))
+        }
+        _ => unreachable!(),
+    });
+    let mut proxy = StoreAdminProxy::new(servant::Context::default(), StoreEntity::NAME_VALUE, &t);
+
+    // 通过Deref调用父接口Store的方法
+    futures::executor::block_on(async {
+        let key = StoreEntity::KEY_VALUE.to_string();
+        proxy
+            .set(key.clone(), StoreEntity::VALUE_VALUE.to_string())
+            .await
+            .unwrap();
+        let v = proxy.get(key.clone()).await.unwrap();
+        assert_eq!(v.as_deref(), Some(StoreEntity::VALUE_VALUE));
+        assert_eq!(proxy.clear().await.unwrap(), 1);
+        assert_eq!(proxy.get(key).await.unwrap(), None);
+    });
+}
+
+#[test]
+fn build_05_inherit() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/05_inherit.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(StoreRequest);
+    show_type!(StoreAdminRequest);
+    show_type!(StoreAdminProxy);
+}
@@ -0,0 +1,389 @@
+// -- 06_grpc.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface(grpc = true)]
+/// simple calculator.
+pub trait Calc {
+    /// add a and b.
+    fn add(&self, a: i32, b: u8) -> i64;
+    fn push(&mut self, values: Vec<i64>, tag: Option<String>);
+    fn history(&self, range: (usize, usize)) -> Vec<(i64, Option<String>)>;
+    fn trace(&self) -> u64;
+}
+
+#[servant::invoke_interface(grpc = true, max_frame_size = 32)]
+pub trait Quota {
+    #[servant(validate = "validate::take", rate_limit = "3/h")]
+    fn take(&self, n: u32) -> u32;
+    fn note(&self, text: String);
+    // 参数与生成的代码中的名字相同
+    fn echo(&self, req: String, msg: u32, ctx: i32, v: u64) -> String;
+}
+
+mod validate {
+    pub fn take(n: &u32) -> servant::ServantResult<()> {
+        if *n == 0 {
+            Err("n is zero.".into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// --
+
+#[derive(Default)]
+struct CalcEntity(Vec<(i64, Option<String>)>);
+impl CalcEntity {
+    const NAME_VALUE: &'static str = "calc1";
+}
+impl Calc for CalcEntity {
+    fn add(&self, _ctx: Option<servant::Context>, a: i32, b: u8) -> i64 {
+        a as i64 + b as i64
+    }
+    fn push(&mut self, _ctx: Option<servant::Context>, values: Vec<i64>, tag: Option<String>) {
+        self.0.extend(values.into_iter().map(|v| (v, tag.clone())));
+    }
+    fn history(
+        &self,
+        _ctx: Option<servant::Context>,
+        range: (usize, usize),
+    ) -> Vec<(i64, Option<String>)> {
+        self.0[range.0..range.1].to_vec()
+    }
+    fn trace(&self, ctx: Option<servant::Context>) -> u64 {
+        ctx.and_then(|ctx| ctx.trace_id()).unwrap_or(0)
+    }
+}
+
+struct QuotaEntity;
+impl Quota for QuotaEntity {
+    fn take(&self, _ctx: Option<servant::Context>, n: u32) -> u32 {
+        n
+    }
+    fn note(&self, _ctx: Option<servant::Context>, _text: String) {}
+    fn echo(
+        &self,
+        _ctx: Option<servant::Context>,
+        req: String,
+        msg: u32,
+        ctx: i32,
+        v: u64,
+    ) -> String {
+        format!("{}{}{}{}", req, msg, ctx, v)
+    }
+}
+
+// --
+
+fn grpc() -> (
+    std::sync::Arc<CalcServant<CalcEntity>>,
+    CalcGrpc<CalcEntity>,
+) {
+    let servant = std::sync::Arc::new(CalcServant::new(
+        CalcEntity::NAME_VALUE,
+        CalcEntity::default(),
+    ));
+    (servant.clone(), CalcGrpc::new(servant))
+}
+
+#[test]
+fn test_calc_proto() {
+    show_type!(CalcGrpc<CalcEntity>);
+    let proto = CalcGrpc::<CalcEntity>::PROTO;
+    assert!(proto.contains("package calc;"));
+    assert!(proto.contains("// simple calculator.\nservice Calc {"));
+    assert!(proto.contains("  // add a and b.\n  rpc Add(AddRequest) returns (AddReply);"));
+    assert!(proto.contains("message AddRequest {\n  int32 a = 1;\n  uint32 b = 2;\n}"));
+    assert!(proto.contains(
+        "message PushRequest {\n  repeated int64 values = 1;\n  optional string tag = 2;\n}"
+    ));
+    assert!(proto.contains("message PushReply {\n}"));
+    assert!(proto.contains("message HistoryReply {\n  bytes value = 1;\n}"));
+}
+
+#[test]
+fn test_calc_grpc() {
+    use prost::Message;
+    use servant::GrpcService;
+
+    let (_, g) = grpc();
+    assert_eq!(g.service_name(), "calc.Calc");
+
+    let req = calc_grpc::AddRequest { a: 40, b: 2 }.encode_to_vec();
+    let reply = calc_grpc::AddReply::decode(&g.call(None, "Add", &req).unwrap()[..]).unwrap();
+    assert_eq!(reply.value, 42);
+
+    let req = calc_grpc::AddRequest { a: 1, b: 256 }.encode_to_vec();
+    assert!(g.call(None, "Add", &req).is_err());
+    assert!(g.call(None, "Sub", &req).is_err());
+}
+
+#[test]
+fn test_calc_grpc_shared_entity() {
+    use prost::Message;
+    use servant::{GrpcService, Servant};
+
+    let (s, g) = grpc();
+    let req = calc_grpc::PushRequest {
+        values: vec![1, 2],
+        tag: Some("grpc".to_string()),
+    }
+    .encode_to_vec();
+    calc_grpc::PushReply::decode(&g.call(None, "Push", &req).unwrap()[..]).unwrap();
+
+    let req = CalcFrame::Call(CalcRequest::Push {
+        values: vec![3],
+        tag: None,
+    });
+    let reps = s.serve(None, bincode::serialize(&req).unwrap());
+    assert!(bincode::deserialize::<servant::ServantResult<()>>(&reps)
+        .unwrap()
+        .is_ok());
+
+    let req = calc_grpc::HistoryRequest {
+        range: bincode::serialize(&(1usize, 3usize)).unwrap(),
+    }
+    .encode_to_vec();
+    let reply =
+        calc_grpc::HistoryReply::decode(&g.call(None, "History", &req).unwrap()[..]).unwrap();
+    let history: Vec<(i64, Option<String>)> = bincode::deserialize(&reply.value).unwrap();
+    assert_eq!(history, vec![(2, Some("grpc".to_string())), (3, None)]);
+}
+
+// gRPC的请求与bincode的请求一样经过validate、rate_limit、max_frame_size和metrics
+#[test]
+fn test_quota_grpc_dispatch() {
+    use prost::Message;
+    use servant::GrpcService;
+
+    let g = QuotaGrpc::new(std::sync::Arc::new(QuotaServant::new(
+        "quota1",
+        QuotaEntity,
+    )));
+    let mut ctx = servant::Context::default();
+    ctx.set_peer("10.0.0.1:1000".parse().unwrap());
+    let take = |n| {
+        let req = quota_grpc::TakeRequest { n }.encode_to_vec();
+        g.call(Some(ctx.clone()), "Take", &req)
+            .map(|reply| quota_grpc::TakeReply::decode(&reply[..]).unwrap().value)
+    };
+
+    // 先检查rate_limit，不合法的请求也计数
+    assert_eq!(take(0).unwrap_err().to_string(), "n is zero.");
+    assert_eq!(take(1).unwrap(), 1);
+    assert_eq!(take(2).unwrap(), 2);
+    assert!(take(3).unwrap_err().retry_after_ms().is_some());
+
+    let req = quota_grpc::NoteRequest {
+        text: "x".repeat(8),
+    }
+    .encode_to_vec();
+    assert!(g.call(None, "Note", &req).is_ok());
+    let req = quota_grpc::NoteRequest {
+        text: "x".repeat(64),
+    }
+    .encode_to_vec();
+    let e = g.call(None, "Note", &req).unwrap_err();
+    assert!(e.to_string().contains("max_frame_size"));
+
+    let req = quota_grpc::EchoRequest {
+        req: "r".to_string(),
+        msg: 1,
+        ctx: -2,
+        v: 3,
+    }
+    .encode_to_vec();
+    let reply = g.call(None, "Echo", &req).unwrap();
+    assert_eq!(
+        quota_grpc::EchoReply::decode(&reply[..]).unwrap().value,
+        "r1-23"
+    );
+
+    let metrics: Vec<_> = servant::metrics::registry()
+        .dump()
+        .into_iter()
+        .filter(|m| m.category == "Quota")
+        .collect();
+    let take = metrics.iter().find(|m| m.method == "take").unwrap();
+    assert_eq!((take.calls, take.errors), (4, 2));
+    // request_bytes是protobuf请求的长度，n = 0时是空的消息
+    assert_eq!(take.request_bytes, 2 * 3);
+}
+
+// --
+
+// 测试用的gRPC服务器：请求和响应按照bytes转发给CalcGrpc，metadata中的servant-trace-id放到Context中
+#[derive(Default)]
+struct RawCodec;
+impl tonic::codec::Codec for RawCodec {
+    type Encode = Vec<u8>;
+    type Decode = Vec<u8>;
+    type Encoder = RawCodec;
+    type Decoder = RawCodec;
+    fn encoder(&mut self) -> Self::Encoder {
+        RawCodec
+    }
+    fn decoder(&mut self) -> Self::Decoder {
+        RawCodec
+    }
+}
+impl tonic::codec::Encoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = tonic::Status;
+    fn encode(
+        &mut self,
+        item: Vec<u8>,
+        dst: &mut tonic::codec::EncodeBuf<'_>,
+    ) -> Result<(), Self::Error> {
+        prost::bytes::BufMut::put_slice(dst, &item);
+        Ok(())
+    }
+}
+impl tonic::codec::Decoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = tonic::Status;
+    fn decode(
+        &mut self,
+        src: &mut tonic::codec::DecodeBuf<'_>,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        use prost::bytes::Buf;
+        Ok(Some(src.copy_to_bytes(src.remaining()).to_vec()))
+    }
+}
+
+#[derive(Clone)]
+struct GrpcServer(std::sync::Arc<CalcGrpc<CalcEntity>>);
+impl tonic::server::NamedService for GrpcServer {
+    const NAME: &'static str = "calc.Calc";
+}
+
+struct GrpcMethod(std::sync::Arc<CalcGrpc<CalcEntity>>, String);
+impl tonic::server::UnaryService<Vec<u8>> for GrpcMethod {
+    type Response = Vec<u8>;
+    type Future = tonic::codegen::BoxFuture<tonic::Response<Vec<u8>>, tonic::Status>;
+    fn call(&mut self, request: tonic::Request<Vec<u8>>) -> Self::Future {
+        use servant::GrpcService;
+
+        let mut ctx = servant::Context::default();
+        if let Some(id) = request
+            .metadata()
+            .get("servant-trace-id")
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+        {
+            ctx.set_trace_id(id);
+        }
+        let reply = self
+            .0
+            .call(Some(ctx), &self.1, request.get_ref())
+            .map(tonic::Response::new)
+            .map_err(|e| tonic::Status::internal(e.to_string()));
+        Box::pin(async move { reply })
+    }
+}
+
+impl<B> tonic::codegen::Service<tonic::codegen::http::Request<B>> for GrpcServer
+where
+    B: tonic::codegen::Body + Send + 'static,
+    B::Error: Into<tonic::codegen::StdError> + Send + 'static,
+{
+    type Response = tonic::codegen::http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = tonic::codegen::BoxFuture<Self::Response, Self::Error>;
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+    fn call(&mut self, req: tonic::codegen::http::Request<B>) -> Self::Future {
+        let method = GrpcMethod(
+            self.0.clone(),
+            req.uri().path().rsplit('/').next().unwrap().to_string(),
+        );
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(RawCodec);
+            Ok(grpc.unary(method, req).await)
+        })
+    }
+}
+
+async fn grpc_unary(
+    channel: tonic::transport::Channel,
+    method: &str,
+    req: Vec<u8>,
+    trace_id: Option<u64>,
+) -> Result<Vec<u8>, tonic::Status> {
+    let mut client = tonic::client::Grpc::new(channel);
+    client.ready().await.unwrap();
+    let mut request = tonic::Request::new(req);
+    if let Some(id) = trace_id {
+        request
+            .metadata_mut()
+            .insert("servant-trace-id", id.to_string().parse().unwrap());
+    }
+    let path = format!("/calc.Calc/{}", method).parse().unwrap();
+    client
+        .unary(request, path, RawCodec)
+        .await
+        .map(tonic::Response::into_inner)
+}
+
+#[tokio::test]
+async fn test_calc_grpc_loopback() {
+    use prost::Message;
+
+    let (_, g) = grpc();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let incoming =
+        tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(GrpcServer(std::sync::Arc::new(g)))
+            .serve_with_incoming(incoming),
+    );
+    let channel = tonic::transport::Channel::from_shared(format!("http://{}", addr))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+
+    let req = calc_grpc::AddRequest { a: 40, b: 2 }.encode_to_vec();
+    let reply = grpc_unary(channel.clone(), "Add", req, None).await.unwrap();
+    assert_eq!(calc_grpc::AddReply::decode(&reply[..]).unwrap().value, 42);
+
+    // Context经过gRPC的metadata传给实现类的方法
+    let req = calc_grpc::TraceRequest {}.encode_to_vec();
+    let reply = grpc_unary(channel.clone(), "Trace", req, Some(7))
+        .await
+        .unwrap();
+    assert_eq!(calc_grpc::TraceReply::decode(&reply[..]).unwrap().value, 7);
+
+    let req = calc_grpc::AddRequest { a: 1, b: 256 }.encode_to_vec();
+    let status = grpc_unary(channel.clone(), "Add", req.clone(), None)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Internal);
+    let status = grpc_unary(channel, "Sub", req, None).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Internal);
+}
+
+#[test]
+fn build_06_grpc() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/06_grpc.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(CalcRequest);
+    show_type!(CalcGrpc<CalcEntity>);
+    show_type!(QuotaGrpc<QuotaEntity>);
+}
@@ -0,0 +1,165 @@
+// -- 07_http.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface(max_frame_size = 64)]
+pub trait Account {
+    fn deposit(&mut self, amount: u64, memo: Option<String>) -> u64;
+    fn balance(&self) -> u64;
+    fn fail(&self);
+    fn owners(&self) -> std::collections::HashMap<(u8, u8), u8>;
+}
+
+#[servant::watch_interface(max_frame_size = 32)]
+pub trait Status {
+    fn uptime(&self, unit: String) -> (u64, String);
+}
+
+// --
+
+#[derive(Default)]
+struct AccountEntity(u64);
+impl AccountEntity {
+    const NAME_VALUE: &'static str = "account1";
+}
+impl Account for AccountEntity {
+    fn deposit(
+        &mut self,
+        _ctx: Option<servant::Context>,
+        amount: u64,
+        _memo: Option<String>,
+    ) -> u64 {
+        self.0 += amount;
+        self.0
+    }
+    fn balance(&self, _ctx: Option<servant::Context>) -> u64 {
+        self.0
+    }
+    fn fail(&self, _ctx: Option<servant::Context>) {
+        panic!("failed");
+    }
+    fn owners(&self, _ctx: Option<servant::Context>) -> std::collections::HashMap<(u8, u8), u8> {
+        vec![((1, 2), 3)].into_iter().collect()
+    }
+}
+
+struct StatusEntity;
+impl Status for StatusEntity {
+    fn uptime(&self, unit: String) -> (u64, String) {
+        (42, unit)
+    }
+}
+
+// --
+
+fn json(body: &[u8]) -> serde_json::Value {
+    serde_json::from_slice(body).unwrap()
+}
+
+#[test]
+fn test_account_http() {
+    use servant::HttpServant;
+
+    show_type!(AccountServant<AccountEntity>);
+    let s = AccountServant::new(AccountEntity::NAME_VALUE, AccountEntity::default());
+
+    let (status, body) = s.serve_http(
+        "POST",
+        "/Account/account1/deposit",
+        br#"{"amount": 40, "memo": "salary"}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(json(&body), serde_json::json!(40));
+
+    let (status, body) = s.serve_http("POST", "/Account/account1/deposit", br#"{"amount": 2}"#);
+    assert_eq!(status, 200);
+    assert_eq!(json(&body), serde_json::json!(42));
+
+    let (status, body) = s.serve_http("POST", "/Account/account1/balance", b"");
+    assert_eq!(status, 200);
+    assert_eq!(json(&body), serde_json::json!(42));
+}
+
+#[test]
+fn test_account_http_error() {
+    use servant::HttpServant;
+
+    let s = AccountServant::new(AccountEntity::NAME_VALUE, AccountEntity::default());
+
+    assert_eq!(s.serve_http("GET", "/Account/account1/balance", b"").0, 405);
+    assert_eq!(
+        s.serve_http("POST", "/Account/account2/balance", b"").0,
+        404
+    );
+    assert_eq!(s.serve_http("POST", "/Status/account1/balance", b"").0, 404);
+    assert_eq!(
+        s.serve_http("POST", "/Account/account1/withdraw", b"").0,
+        404
+    );
+    assert_eq!(s.serve_http("POST", "/Account/balance", b"").0, 404);
+    assert_eq!(
+        s.serve_http("POST", "/Account/account1/deposit", b"{").0,
+        400
+    );
+    assert_eq!(
+        s.serve_http("POST", "/Account/account1/deposit", br#"{"amount": "1"}"#)
+            .0,
+        400
+    );
+
+    let (status, body) = s.serve_http("POST", "/Account/account1/fail", b"{}");
+    assert_eq!(status, 500);
+    assert!(json(&body)["error"].is_string());
+
+    // 返回值不能编码成JSON
+    let (status, body) = s.serve_http("POST", "/Account/account1/owners", b"{}");
+    assert_eq!(status, 500);
+    assert!(json(&body)["error"].is_string());
+
+    // 超过max_frame_size的body不解码
+    let body = format!(r#"{{"amount": 1, "memo": "{}"}}"#, "x".repeat(64));
+    let (status, body) = s.serve_http("POST", "/Account/account1/deposit", body.as_bytes());
+    assert_eq!(status, 413);
+    assert!(json(&body)["error"]
+        .as_str()
+        .unwrap()
+        .contains("max_frame_size"));
+    assert_eq!(
+        s.serve_http("POST", "/Account/account1/balance", b"").1,
+        b"0"
+    );
+}
+
+#[test]
+fn test_status_http() {
+    use servant::HttpServant;
+
+    let s = StatusServant::new(StatusEntity);
+    let (status, body) = s.serve_http("POST", "/Status/any/uptime", br#"{"unit": "s"}"#);
+    assert_eq!(status, 200);
+    assert_eq!(json(&body), serde_json::json!([42, "s"]));
+    assert_eq!(s.serve_http("POST", "/Status/any/uptime", b"{}").0, 400);
+    let body = format!(r#"{{"unit": "{}"}}"#, "s".repeat(32));
+    assert_eq!(
+        s.serve_http("POST", "/Status/any/uptime", body.as_bytes())
+            .0,
+        413
+    );
+}
+
+#[test]
+fn build_07_http() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/07_http.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(AccountRequest);
+    show_type!(StatusRequest);
+}
@@ -0,0 +1,213 @@
+// -- 08_jsonrpc.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface(max_frame_size = 512)]
+pub trait Counter {
+    fn add(&mut self, step: i64, note: Option<String>) -> i64;
+    fn get(&self) -> i64;
+    fn fail(&self);
+    fn owners(&self) -> std::collections::HashMap<(u8, u8), u8>;
+}
+
+#[servant::watch_interface(max_frame_size = 96)]
+pub trait Clock {
+    fn now(&self, zone: String) -> String;
+}
+
+// --
+
+#[derive(Default)]
+struct CounterEntity(i64);
+impl CounterEntity {
+    const NAME_VALUE: &'static str = "counter1";
+}
+impl Counter for CounterEntity {
+    fn add(&mut self, _ctx: Option<servant::Context>, step: i64, _note: Option<String>) -> i64 {
+        self.0 += step;
+        self.0
+    }
+    fn get(&self, _ctx: Option<servant::Context>) -> i64 {
+        self.0
+    }
+    fn fail(&self, _ctx: Option<servant::Context>) {
+        panic!("failed");
+    }
+    fn owners(&self, _ctx: Option<servant::Context>) -> std::collections::HashMap<(u8, u8), u8> {
+        vec![((1, 2), 3)].into_iter().collect()
+    }
+}
+
+struct ClockEntity;
+impl Clock for ClockEntity {
+    fn now(&self, zone: String) -> String {
+        format!("12:00 {}", zone)
+    }
+}
+
+// --
+
+fn call(s: &impl servant::JsonRpcServant, req: &str) -> Option<serde_json::Value> {
+    s.serve_jsonrpc(req.as_bytes())
+        .map(|reps| serde_json::from_slice(&reps).unwrap())
+}
+
+fn counter() -> CounterServant<CounterEntity> {
+    CounterServant::new(CounterEntity::NAME_VALUE, CounterEntity::default())
+}
+
+#[test]
+fn test_counter_jsonrpc() {
+    show_type!(CounterServant<CounterEntity>);
+    let s = counter();
+
+    let reps = call(
+        &s,
+        r#"{"jsonrpc": "2.0", "method": "Counter.add", "params": {"step": 40, "note": "x"}, "id": 1}"#,
+    );
+    assert_eq!(
+        reps,
+        Some(serde_json::json!({"jsonrpc": "2.0", "result": 40, "id": 1}))
+    );
+
+    let reps = call(
+        &s,
+        r#"{"jsonrpc": "2.0", "method": "Counter.add", "params": [2, null], "id": "a"}"#,
+    );
+    assert_eq!(
+        reps,
+        Some(serde_json::json!({"jsonrpc": "2.0", "result": 42, "id": "a"}))
+    );
+
+    let reps = call(
+        &s,
+        r#"{"jsonrpc": "2.0", "method": "Counter.get", "id": 2}"#,
+    );
+    assert_eq!(reps.unwrap()["result"], 42);
+
+    // notification没有响应，但是方法仍然被调用
+    let reps = call(
+        &s,
+        r#"{"jsonrpc": "2.0", "method": "Counter.add", "params": [1, null]}"#,
+    );
+    assert_eq!(reps, None);
+    let reps = call(
+        &s,
+        r#"{"jsonrpc": "2.0", "method": "Counter.get", "id": 3}"#,
+    );
+    assert_eq!(reps.unwrap()["result"], 43);
+}
+
+#[test]
+fn test_counter_jsonrpc_error() {
+    let s = counter();
+    let code = |req: &str| call(&s, req).unwrap()["error"]["code"].clone();
+
+    assert_eq!(code(r#"{"jsonrpc": "2.0", "method""#), -32700);
+    assert_eq!(code(r#"{"method": "Counter.get", "id": 1}"#), -32600);
+    assert_eq!(code(r#"{"jsonrpc": "2.0", "method": 1, "id": 1}"#), -32600);
+    assert_eq!(code(r#"[]"#), -32600);
+    assert_eq!(
+        code(r#"{"jsonrpc": "2.0", "method": "Counter.sub", "id": 1}"#),
+        -32601
+    );
+    assert_eq!(
+        code(r#"{"jsonrpc": "2.0", "method": "Clock.now", "id": 1}"#),
+        -32601
+    );
+    assert_eq!(
+        code(r#"{"jsonrpc": "2.0", "method": "Counter.add", "params": [1], "id": 1}"#),
+        -32602
+    );
+    assert_eq!(
+        code(r#"{"jsonrpc": "2.0", "method": "Counter.add", "params": {"step": "1"}, "id": 1}"#),
+        -32602
+    );
+    assert_eq!(
+        code(r#"{"jsonrpc": "2.0", "method": "Counter.fail", "id": 1}"#),
+        -32000
+    );
+    assert_eq!(
+        code(r#"{"jsonrpc": "2.0", "method": "Counter.owners", "id": 1}"#),
+        -32000
+    );
+
+    let reps = call(&s, r#"{"jsonrpc": "2.0", "method": "Counter.sub"}"#);
+    assert_eq!(reps, None);
+}
+
+#[test]
+fn test_counter_jsonrpc_batch() {
+    let s = counter();
+    let reps = call(
+        &s,
+        r#"[
+            {"jsonrpc": "2.0", "method": "Counter.add", "params": [5, null], "id": 1},
+            {"jsonrpc": "2.0", "method": "Counter.add", "params": [5, null]},
+            {"jsonrpc": "2.0", "method": "Counter.get", "id": 2},
+            1
+        ]"#,
+    );
+    assert_eq!(
+        reps,
+        Some(serde_json::json!([
+            {"jsonrpc": "2.0", "result": 5, "id": 1},
+            {"jsonrpc": "2.0", "result": 10, "id": 2},
+            {"jsonrpc": "2.0", "error": {"code": -32600, "message": "invalid request."}, "id": null},
+        ]))
+    );
+
+    let reps = call(&s, r#"[{"jsonrpc": "2.0", "method": "Counter.get"}]"#);
+    assert_eq!(reps, None);
+
+    // 超过max_frame_size时整个batch都不解码
+    let req = format!(
+        r#"[{{"jsonrpc": "2.0", "method": "Counter.add", "params": [1, "{}"], "id": 3}}]"#,
+        "x".repeat(512)
+    );
+    let reps = call(&s, &req).unwrap();
+    assert_eq!(reps["error"]["code"], -32600);
+    assert!(reps["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("max_frame_size"));
+    assert_eq!(reps["id"], serde_json::Value::Null);
+    let reps = call(
+        &s,
+        r#"{"jsonrpc": "2.0", "method": "Counter.get", "id": 4}"#,
+    );
+    assert_eq!(reps.unwrap()["result"], 10);
+}
+
+#[test]
+fn test_clock_jsonrpc() {
+    let s = ClockServant::new(ClockEntity);
+    let reps = call(
+        &s,
+        r#"{"jsonrpc": "2.0", "method": "Clock.now", "params": ["UTC"], "id": 1}"#,
+    );
+    assert_eq!(reps.unwrap()["result"], "12:00 UTC");
+
+    let req = format!(
+        r#"{{"jsonrpc": "2.0", "method": "Clock.now", "params": ["{}"], "id": 2}}"#,
+        "Z".repeat(96)
+    );
+    assert_eq!(call(&s, &req).unwrap()["error"]["code"], -32600);
+}
+
+#[test]
+fn build_08_jsonrpc() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/08_jsonrpc.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(CounterRequest);
+    show_type!(ClockRequest);
+}
@@ -0,0 +1,310 @@
+// -- 09_tracing.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface]
+pub trait Vault {
+    fn login(&self, user: String, #[servant(redact)] password: String) -> bool;
+    fn fail(&self, code: i32);
+    fn tag(&self, category: String, method: u8, span: u32) -> String;
+    fn shadow(
+        &self,
+        entity: String,
+        ctx: u32,
+        req: u32,
+        metrics_start: u32,
+        trace_ctx: u32,
+    ) -> String;
+}
+
+#[servant::report_interface]
+pub trait Audit {
+    fn record(&self, #[servant(redact)] token: String, action: String);
+    fn note(&self, category: String, span: u32);
+    fn shadow(&self, entity: String, req: u32, metrics_start: u32, reps: u32);
+}
+
+#[servant::watch_interface]
+pub trait Gauge {
+    fn shadow(&self, entity: String, req: u32, metrics_start: u32, reps: u32) -> String;
+}
+
+#[servant::notify_interface]
+pub trait Pulse {
+    fn shadow(&mut self, entity: String, req: u32, metrics_start: u32, reps: u32);
+}
+
+// --
+
+struct VaultEntity;
+impl Vault for VaultEntity {
+    fn login(&self, _ctx: Option<servant::Context>, user: String, password: String) -> bool {
+        user == "admin" && password == "secret"
+    }
+    fn fail(&self, _ctx: Option<servant::Context>, code: i32) {
+        panic!("failed with {}", code);
+    }
+    fn tag(
+        &self,
+        _ctx: Option<servant::Context>,
+        category: String,
+        method: u8,
+        span: u32,
+    ) -> String {
+        format!("{}.{}.{}", category, method, span)
+    }
+    fn shadow(
+        &self,
+        _ctx: Option<servant::Context>,
+        entity: String,
+        ctx: u32,
+        req: u32,
+        metrics_start: u32,
+        trace_ctx: u32,
+    ) -> String {
+        format!("{}{}{}{}{}", entity, ctx, req, metrics_start, trace_ctx)
+    }
+}
+
+struct AuditEntity;
+impl Audit for AuditEntity {
+    fn record(&self, _token: String, _action: String) {}
+    fn note(&self, _category: String, _span: u32) {}
+    fn shadow(&self, _entity: String, _req: u32, _metrics_start: u32, _reps: u32) {}
+}
+
+struct GaugeEntity;
+impl Gauge for GaugeEntity {
+    fn shadow(&self, entity: String, req: u32, metrics_start: u32, reps: u32) -> String {
+        format!("{}{}{}{}", entity, req, metrics_start, reps)
+    }
+}
+
+struct PulseEntity(std::sync::Arc<std::sync::Mutex<String>>);
+impl Pulse for PulseEntity {
+    fn shadow(&mut self, entity: String, req: u32, metrics_start: u32, reps: u32) {
+        *self.0.lock().unwrap() = format!("{}{}{}{}", entity, req, metrics_start, reps);
+    }
+}
+
+// --
+
+// 记录span和event的字段，用于检查生成的代码
+type Records = Vec<(String, Vec<(String, String)>)>;
+
+#[derive(Clone, Default)]
+struct Recorder(std::sync::Arc<std::sync::Mutex<Records>>);
+
+struct Visitor<'a>(&'a mut Vec<(String, String)>);
+impl tracing::field::Visit for Visitor<'_> {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+}
+
+impl tracing::Subscriber for Recorder {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut fields = Vec::new();
+        span.record(&mut Visitor(&mut fields));
+        let mut records = self.0.lock().unwrap();
+        records.push((span.metadata().name().to_string(), fields));
+        tracing::span::Id::from_u64(records.len() as u64)
+    }
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut fields = Vec::new();
+        event.record(&mut Visitor(&mut fields));
+        self.0.lock().unwrap().push(("event".to_string(), fields));
+    }
+    fn enter(&self, _: &tracing::span::Id) {}
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+impl Recorder {
+    fn records(&self) -> Records {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    fields.iter().find(|f| f.0 == name).map(|f| f.1.as_str())
+}
+
+// --
+
+#[test]
+fn test_vault_servant_span() {
+    use servant::Servant;
+
+    show_type!(VaultServant<VaultEntity>);
+    let recorder = Recorder::default();
+    let s = VaultServant::new("vault1", VaultEntity);
+    let mut ctx = servant::Context::default();
+    ctx.set_trace_id(7);
+
+    let req = VaultFrame::Call(VaultRequest::Login {
+        user: "admin".to_string(),
+        password: "secret".to_string(),
+    });
+    let reps = tracing::subscriber::with_default(recorder.clone(), || {
+        s.serve(Some(ctx), bincode::serialize(&req).unwrap())
+    });
+    assert!(bincode::deserialize::<servant::ServantResult<bool>>(&reps)
+        .unwrap()
+        .unwrap());
+
+    let records = recorder.records();
+    let (name, fields) = &records[0];
+    assert_eq!(name, "Vault.login");
+    assert_eq!(field(fields, "servant.category"), Some("\"Vault\""));
+    assert_eq!(field(fields, "servant.method"), Some("\"login\""));
+    assert_eq!(field(fields, "servant.trace_id"), Some("7"));
+    assert_eq!(field(fields, "user"), Some("\"admin\""));
+    assert_eq!(field(fields, "password"), Some("\"<redacted>\""));
+    assert_eq!(field(&records[1].1, "message"), Some("call completed"));
+}
+
+#[test]
+fn test_vault_servant_failed_event() {
+    use servant::Servant;
+
+    let recorder = Recorder::default();
+    let s = VaultServant::new("vault1", VaultEntity);
+    let req = VaultFrame::Call(VaultRequest::Fail { code: 3 });
+    tracing::subscriber::with_default(recorder.clone(), || {
+        s.serve(None, bincode::serialize(&req).unwrap())
+    });
+
+    let records = recorder.records();
+    assert_eq!(records[0].0, "Vault.fail");
+    assert_eq!(field(&records[0].1, "servant.trace_id"), Some("0"));
+    assert_eq!(field(&records[0].1, "code"), Some("3"));
+    assert_eq!(field(&records[1].1, "message"), Some("call failed"));
+    assert!(field(&records[1].1, "error")
+        .unwrap()
+        .contains("failed with 3"));
+}
+
+// 与生成的字段和局部变量同名的参数
+#[test]
+fn test_vault_servant_param_names() {
+    use servant::Servant;
+
+    let recorder = Recorder::default();
+    let s = VaultServant::new("vault1", VaultEntity);
+    let req = VaultFrame::Call(VaultRequest::Tag {
+        category: "c".to_string(),
+        method: 2,
+        span: 3,
+    });
+    let reps = tracing::subscriber::with_default(recorder.clone(), || {
+        s.serve(None, bincode::serialize(&req).unwrap())
+    });
+    assert_eq!(
+        bincode::deserialize::<servant::ServantResult<String>>(&reps)
+            .unwrap()
+            .unwrap(),
+        "c.2.3"
+    );
+
+    let records = recorder.records();
+    let fields = &records[0].1;
+    assert_eq!(field(fields, "servant.category"), Some("\"Vault\""));
+    assert_eq!(field(fields, "servant.method"), Some("\"tag\""));
+    assert_eq!(field(fields, "category"), Some("\"c\""));
+    assert_eq!(field(fields, "method"), Some("2"));
+    assert_eq!(field(fields, "span"), Some("3"));
+}
+
+// 参数与生成的代码中的局部变量同名，servant、proxy、officer、watch和receiver都不受影响
+#[test]
+fn test_shadowing_param_names() {
+    use servant::{NotifyServant, ReportServant, Servant, WatchServant};
+
+    let s = std::sync::Arc::new(VaultServant::new("vault1", VaultEntity));
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Invoke { ctx, req, .. } => Ok(s.serve(ctx, req)),
+        _ => Err("unexpected call".to_string().into()),
+    });
+    let mut proxy = VaultProxy::new(servant::Context::default(), "vault1", &t);
+    let r = futures::executor::block_on(proxy.shadow("e".to_string(), 1, 2, 3, 4));
+    assert_eq!(r.unwrap(), "e1234");
+
+    let s = AuditOfficer::new("audit1", AuditEntity);
+    let req = AuditFrame::Report(AuditRequest::Shadow {
+        entity: "e".to_string(),
+        req: 1,
+        metrics_start: 2,
+        reps: 3,
+    });
+    s.serve(bincode::serialize(&req).unwrap());
+
+    let s = GaugeServant::new(GaugeEntity);
+    let req = GaugeRequest::Shadow {
+        entity: "e".to_string(),
+        req: 1,
+        metrics_start: 2,
+        reps: 3,
+    };
+    let reps = s.serve(bincode::serialize(&req).unwrap());
+    assert_eq!(
+        bincode::deserialize::<servant::ServantResult<String>>(&reps)
+            .unwrap()
+            .unwrap(),
+        "e123"
+    );
+
+    let state = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    let mut receiver = PulseReceiver::new(PulseEntity(state.clone()));
+    let req = PulseRequest::Shadow {
+        entity: "e".to_string(),
+        req: 1,
+        metrics_start: 2,
+        reps: 3,
+    };
+    receiver.serve(bincode::serialize(&req).unwrap());
+    assert_eq!(*state.lock().unwrap(), "e123");
+}
+
+#[test]
+fn test_audit_officer_span() {
+    use servant::ReportServant;
+
+    let recorder = Recorder::default();
+    let s = AuditOfficer::new("audit1", AuditEntity);
+    let req = AuditFrame::Report(AuditRequest::Record {
+        token: "abc".to_string(),
+        action: "open".to_string(),
+    });
+    tracing::subscriber::with_default(recorder.clone(), || {
+        s.serve(bincode::serialize(&req).unwrap())
+    });
+
+    let records = recorder.records();
+    assert_eq!(records[0].0, "Audit.record");
+    assert_eq!(field(&records[0].1, "token"), Some("\"<redacted>\""));
+    assert_eq!(field(&records[0].1, "action"), Some("\"open\""));
+    assert_eq!(field(&records[0].1, "servant.trace_id"), None);
+}
+
+#[test]
+fn build_09_tracing() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/09_tracing.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(VaultRequest);
+    show_type!(AuditRequest);
+}
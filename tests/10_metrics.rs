@@ -0,0 +1,184 @@
+// -- 10_metrics.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface]
+pub trait Shop {
+    fn buy(&mut self, item: String, count: u32) -> u32;
+    fn fail(&self);
+    #[servant(validate = "validate::sell")]
+    fn sell(&mut self, count: u32) -> u32;
+    #[servant(rate_limit = "1/h")]
+    fn restock(&mut self);
+}
+
+mod validate {
+    pub fn sell(count: &u32) -> servant::ServantResult<()> {
+        if *count == 0 {
+            Err("count is zero.".into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[servant::report_interface]
+pub trait Click {
+    fn click(&self, x: i32, y: i32);
+}
+
+#[servant::notify_interface]
+pub trait Alarm {
+    fn ring(&self, level: u8);
+}
+
+#[servant::watch_interface]
+pub trait Monitor {
+    fn metrics(&self) -> Vec<servant::metrics::MethodMetrics>;
+}
+
+// --
+
+#[derive(Default)]
+struct ShopEntity(u32);
+impl Shop for ShopEntity {
+    fn buy(&mut self, _ctx: Option<servant::Context>, _item: String, count: u32) -> u32 {
+        self.0 += count;
+        self.0
+    }
+    fn fail(&self, _ctx: Option<servant::Context>) {
+        panic!("failed");
+    }
+    fn sell(&mut self, _ctx: Option<servant::Context>, count: u32) -> u32 {
+        self.0 -= count;
+        self.0
+    }
+    fn restock(&mut self, _ctx: Option<servant::Context>) {}
+}
+
+struct ClickEntity;
+impl Click for ClickEntity {
+    fn click(&self, _x: i32, _y: i32) {}
+}
+
+// 通过watch接口导出缺省registry中的metrics
+struct MonitorEntity;
+impl Monitor for MonitorEntity {
+    fn metrics(&self) -> Vec<servant::metrics::MethodMetrics> {
+        servant::metrics::registry().dump()
+    }
+}
+
+// --
+
+fn dump(category: &str) -> Vec<servant::metrics::MethodMetrics> {
+    use servant::WatchServant;
+
+    let s = MonitorServant::new(MonitorEntity);
+    let req = bincode::serialize(&MonitorRequest::Metrics {}).unwrap();
+    let reps: servant::ServantResult<Vec<servant::metrics::MethodMetrics>> =
+        bincode::deserialize(&s.serve(req)).unwrap();
+    reps.unwrap()
+        .into_iter()
+        .filter(|m| m.category == category)
+        .collect()
+}
+
+#[test]
+fn test_shop_servant_metrics() {
+    use servant::Servant;
+
+    show_type!(ShopServant<ShopEntity>);
+    let s = ShopServant::new("shop1", ShopEntity::default());
+    let call = |req: ShopRequest| s.serve(None, bincode::serialize(&ShopFrame::Call(req)).unwrap());
+    call(ShopRequest::Buy {
+        item: "apple".to_string(),
+        count: 2,
+    });
+    call(ShopRequest::Buy {
+        item: "pear".to_string(),
+        count: 3,
+    });
+    call(ShopRequest::Fail {});
+
+    let metrics = dump("Shop");
+    let buy = metrics.iter().find(|m| m.method == "buy").unwrap();
+    assert_eq!(buy.side, Some(servant::metrics::Side::Servant));
+    assert_eq!((buy.calls, buy.errors), (2, 0));
+    // 收到的请求的字节数：frame和request的序号、item和count
+    assert_eq!(buy.request_bytes, 2 * (4 + 4 + 8 + 4) + 5 + 4);
+    assert_eq!(
+        buy.response_bytes,
+        2 * bincode::serialized_size(&servant::ServantResult::Ok(5u32)).unwrap()
+    );
+    assert_eq!(buy.latency_buckets_us.iter().map(|b| b.1).sum::<u64>(), 2);
+    let fail = metrics.iter().find(|m| m.method == "fail").unwrap();
+    assert_eq!((fail.calls, fail.errors), (1, 1));
+}
+
+// validate和rate_limit拒绝的请求，也记录为失败的调用
+#[test]
+fn test_shop_servant_rejected_metrics() {
+    use servant::Servant;
+
+    let s = ShopServant::new("shop1", ShopEntity(10));
+    let call = |req: ShopRequest| s.serve(None, bincode::serialize(&ShopFrame::Call(req)).unwrap());
+    call(ShopRequest::Sell { count: 0 });
+    call(ShopRequest::Sell { count: 1 });
+    call(ShopRequest::Restock {});
+    call(ShopRequest::Restock {});
+
+    let metrics = dump("Shop");
+    let sell = metrics.iter().find(|m| m.method == "sell").unwrap();
+    assert_eq!((sell.calls, sell.errors), (2, 1));
+    let restock = metrics.iter().find(|m| m.method == "restock").unwrap();
+    assert_eq!((restock.calls, restock.errors), (2, 1));
+}
+
+#[test]
+fn test_click_officer_metrics() {
+    use servant::ReportServant;
+
+    let s = ClickOfficer::new("click1", ClickEntity);
+    let req = ClickFrame::Report(ClickRequest::Click { x: 1, y: 2 });
+    s.serve(bincode::serialize(&req).unwrap());
+
+    let metrics = dump("Click");
+    assert_eq!(metrics.len(), 1);
+    assert_eq!((metrics[0].calls, metrics[0].errors), (1, 0));
+    assert_eq!(metrics[0].request_bytes, 4 + 4 + 4 + 4);
+    assert_eq!(metrics[0].response_bytes, 0);
+}
+
+// notifier发送失败时记录为失败的调用
+#[test]
+fn test_alarm_notifier_metrics() {
+    let notifier = AlarmNotifier::new(servant::AdapterRegister::with_sender(|_, _, _| {}));
+    futures::executor::block_on(notifier.ring(1)).unwrap();
+    let notifier = AlarmNotifier::new(servant::AdapterRegister::default());
+    assert!(futures::executor::block_on(notifier.ring(2)).is_err());
+
+    let metrics = dump("Alarm");
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(metrics[0].side, Some(servant::metrics::Side::Proxy));
+    assert_eq!((metrics[0].calls, metrics[0].errors), (2, 1));
+}
+
+#[test]
+fn build_10_metrics() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/10_metrics.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(ShopRequest);
+    show_type!(ClickRequest);
+    show_type!(AlarmRequest);
+    show_type!(MonitorRequest);
+}
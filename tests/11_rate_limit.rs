@@ -0,0 +1,203 @@
+// -- 11_rate_limit.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface(retry = 2)]
+pub trait Search {
+    #[servant(rate_limit = "2/s")]
+    fn search(&self, keyword: String) -> Vec<String>;
+    #[servant(rate_limit = "1/h")]
+    fn reindex(&mut self);
+    fn ping(&self) -> bool;
+    // 参数与限流检查中的名字相同
+    #[servant(rate_limit = "1/h")]
+    fn tag(&mut self, limiter: String, ctx: u32) -> String;
+}
+
+// --
+
+struct SearchEntity;
+impl Search for SearchEntity {
+    fn search(&self, _ctx: Option<servant::Context>, keyword: String) -> Vec<String> {
+        vec![keyword]
+    }
+    fn reindex(&mut self, _ctx: Option<servant::Context>) {}
+    fn ping(&self, _ctx: Option<servant::Context>) -> bool {
+        true
+    }
+    fn tag(&mut self, _ctx: Option<servant::Context>, limiter: String, ctx: u32) -> String {
+        format!("{}{}", limiter, ctx)
+    }
+}
+
+// --
+
+fn call(
+    s: &SearchServant<SearchEntity>,
+    peer: Option<&str>,
+    req: SearchRequest,
+) -> servant::ServantResult<()> {
+    use servant::Servant;
+
+    let ctx = peer.map(|addr| {
+        let mut ctx = servant::Context::default();
+        ctx.set_peer(addr.parse().unwrap());
+        ctx
+    });
+    let reps = s.serve(ctx, bincode::serialize(&SearchFrame::Call(req)).unwrap());
+    // 只关心是否成功，Err的编码与返回值的类型无关
+    match bincode::deserialize::<servant::ServantResult<()>>(&reps) {
+        Ok(Err(e)) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn search() -> SearchRequest {
+    SearchRequest::Search {
+        keyword: "rust".to_string(),
+    }
+}
+
+#[test]
+fn test_search_rate_limit() {
+    show_type!(SearchServant<SearchEntity>);
+    let s = SearchServant::new("search1", SearchEntity);
+
+    assert!(call(&s, Some("10.0.0.1:1000"), search()).is_ok());
+    // 同一个地址的不同端口共用配额
+    assert!(call(&s, Some("10.0.0.1:1001"), search()).is_ok());
+    let e = call(&s, Some("10.0.0.1:1000"), search()).unwrap_err();
+    let retry_after_ms = e.retry_after_ms().unwrap();
+    assert!(retry_after_ms > 0 && retry_after_ms <= 1000);
+
+    // 每个地址有自己的配额
+    assert!(call(&s, Some("10.0.0.2:1000"), search()).is_ok());
+    // 没有限流的方法不受影响
+    assert!(call(&s, Some("10.0.0.1:1000"), SearchRequest::Ping {}).is_ok());
+}
+
+#[test]
+fn test_search_rate_limit_client_id() {
+    use servant::Servant;
+
+    // client_id由客户端设置，不能用来绕过限流
+    let s = SearchServant::new("search1", SearchEntity);
+    for client_id in 0..3 {
+        let mut ctx = servant::Context::default();
+        ctx.set_peer("10.0.0.1:1000".parse().unwrap());
+        ctx.set_client_id(client_id);
+        let reps = s.serve(
+            Some(ctx),
+            bincode::serialize(&SearchFrame::Call(search())).unwrap(),
+        );
+        let reps: servant::ServantResult<Vec<String>> = bincode::deserialize(&reps).unwrap();
+        assert_eq!(reps.is_ok(), client_id < 2);
+    }
+}
+
+#[test]
+fn test_reindex_rate_limit() {
+    let s = SearchServant::new("search1", SearchEntity);
+
+    assert!(call(&s, None, SearchRequest::Reindex {}).is_ok());
+    let e = call(&s, None, SearchRequest::Reindex {}).unwrap_err();
+    assert!(e.retry_after_ms().unwrap() > 1000);
+    assert!(call(&s, Some("10.0.0.3:1000"), SearchRequest::Reindex {}).is_ok());
+
+    // 每个servant对象有自己的计数
+    let s2 = SearchServant::new("search2", SearchEntity);
+    assert!(call(&s2, None, SearchRequest::Reindex {}).is_ok());
+}
+
+#[test]
+fn test_tag_rate_limit_shadowing() {
+    // 限流按照servant的Context中对端的地址，不受同名参数的影响
+    let s = SearchServant::new("search1", SearchEntity);
+    let tag = || SearchRequest::Tag {
+        limiter: "v".to_string(),
+        ctx: 1,
+    };
+    assert!(call(&s, Some("10.0.0.1:1000"), tag()).is_ok());
+    assert!(call(&s, Some("10.0.0.1:1000"), tag())
+        .unwrap_err()
+        .retry_after_ms()
+        .is_some());
+    assert!(call(&s, Some("10.0.0.2:1000"), tag()).is_ok());
+}
+
+#[test]
+fn test_search_proxy_throttled() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // 被限流时等待服务端返回的时间后重试，retry = 2，第三次成功
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let t = servant::Terminal::loopback(move |_| {
+        let reps: servant::ServantResult<Vec<String>> = match c.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => Err(servant::ServantError::throttled(50)),
+            _ => Ok(vec!["rust".to_string()]),
+        };
+        Ok(bincode::serialize(&reps).unwrap())
+    });
+    let mut proxy = SearchProxy::new(servant::Context::default(), "search1", &t);
+    let started = std::time::Instant::now();
+    let r = futures::executor::block_on(proxy.search("rust".to_string()));
+    assert_eq!(r.unwrap(), vec!["rust".to_string()]);
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+    assert!(started.elapsed() >= std::time::Duration::from_millis(100));
+
+    // 重试的次数用完，返回限流的错误
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let t = servant::Terminal::loopback(move |_| {
+        c.fetch_add(1, Ordering::SeqCst);
+        let reps: servant::ServantResult<Vec<String>> = Err(servant::ServantError::throttled(1));
+        Ok(bincode::serialize(&reps).unwrap())
+    });
+    let mut proxy = SearchProxy::new(servant::Context::default(), "search1", &t);
+    let r = futures::executor::block_on(proxy.search("rust".to_string()));
+    assert_eq!(r.unwrap_err().retry_after_ms(), Some(1));
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_search_proxy_servant_throttled() {
+    use servant::Servant;
+
+    // proxy经过loopback调用servant，服务端设置对端的地址；第三次被限流，等待后重试成功
+    let s = SearchServant::new("search1", SearchEntity);
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Invoke { ctx, req, .. } => {
+            let mut ctx = ctx.unwrap_or_default();
+            ctx.set_peer("10.0.0.1:1000".parse().unwrap());
+            Ok(s.serve(Some(ctx), req))
+        }
+        _ => unreachable!(),
+    });
+    let mut proxy = SearchProxy::new(servant::Context::default(), "search1", &t);
+    let started = std::time::Instant::now();
+    for _ in 0..3 {
+        let r = futures::executor::block_on(proxy.search("rust".to_string()));
+        assert_eq!(r.unwrap(), vec!["rust".to_string()]);
+    }
+    // 等待到第一次调用的一秒之后
+    assert!(started.elapsed() >= std::time::Duration::from_millis(1000));
+}
+
+#[test]
+fn build_11_rate_limit() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/11_rate_limit.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(SearchRequest);
+    show_type!(SearchProxy);
+}
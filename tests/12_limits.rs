@@ -0,0 +1,137 @@
+// -- 12_limits.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface(max_frame_size = 64)]
+pub trait Store {
+    #[servant(validate = "validate::put")]
+    fn put(&mut self, key: String, value: Vec<u8>) -> usize;
+    fn len(&self) -> usize;
+}
+
+#[servant::report_interface(max_frame_size = 64)]
+pub trait Log {
+    #[servant(validate = "validate::line")]
+    fn line(&self, level: u8, text: String);
+}
+
+mod validate {
+    pub fn put(key: &str, _value: &[u8]) -> servant::ServantResult<()> {
+        if key.is_empty() {
+            return Err("key is empty".into());
+        }
+        Ok(())
+    }
+    pub fn line(level: &u8, _text: &str) -> servant::ServantResult<()> {
+        if *level > 5 {
+            return Err(format!("invalid level {}", level).into());
+        }
+        Ok(())
+    }
+}
+
+// --
+
+#[derive(Default)]
+struct StoreEntity(Vec<(String, Vec<u8>)>);
+impl Store for StoreEntity {
+    fn put(&mut self, _ctx: Option<servant::Context>, key: String, value: Vec<u8>) -> usize {
+        self.0.push((key, value));
+        self.0.len()
+    }
+    fn len(&self, _ctx: Option<servant::Context>) -> usize {
+        self.0.len()
+    }
+}
+
+#[derive(Clone, Default)]
+struct LogEntity(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+impl Log for LogEntity {
+    fn line(&self, _level: u8, text: String) {
+        self.0.lock().unwrap().push(text);
+    }
+}
+
+// --
+
+fn put(s: &StoreServant<StoreEntity>, key: &str, size: usize) -> servant::ServantResult<usize> {
+    use servant::Servant;
+
+    let req = StoreFrame::Call(StoreRequest::Put {
+        key: key.to_string(),
+        value: vec![0; size],
+    });
+    let reps = s.serve(None, bincode::serialize(&req).unwrap());
+    bincode::deserialize(&reps).unwrap()
+}
+
+#[test]
+fn test_store_max_frame_size() {
+    show_type!(StoreServant<StoreEntity>);
+    let s = StoreServant::new("store1", StoreEntity::default());
+
+    assert_eq!(put(&s, "a", 8).unwrap(), 1);
+    let e = put(&s, "b", 64).unwrap_err();
+    assert!(e.to_string().contains("exceeds max_frame_size 64"));
+    assert_eq!(put(&s, "c", 16).unwrap(), 2);
+}
+
+#[test]
+fn test_store_invalid_frame() {
+    use servant::Servant;
+
+    let s = StoreServant::new("store1", StoreEntity::default());
+    // 长度前缀声称有很多元素，但是受max_frame_size的限制，不会分配内存
+    let mut req = bincode::serialize(&StoreFrame::Batch(Vec::new())).unwrap();
+    req[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+    let reps = s.serve(None, req);
+    let e = bincode::deserialize::<servant::ServantResult<()>>(&reps)
+        .unwrap()
+        .unwrap_err();
+    assert!(e.to_string().contains("invalid frame"));
+}
+
+#[test]
+fn test_store_validate() {
+    let s = StoreServant::new("store1", StoreEntity::default());
+
+    let e = put(&s, "", 8).unwrap_err();
+    assert!(e.to_string().contains("key is empty"));
+    assert_eq!(put(&s, "a", 8).unwrap(), 1);
+}
+
+#[test]
+fn test_log_validate() {
+    use servant::ReportServant;
+
+    let entity = LogEntity::default();
+    let s = LogOfficer::new("log1", entity.clone());
+    let line = |level: u8, text: &str| LogRequest::Line {
+        level,
+        text: text.to_string(),
+    };
+    let req = LogFrame::Batch(vec![line(1, "a"), line(9, "b"), line(2, "c")]);
+    s.serve(bincode::serialize(&req).unwrap());
+    // 超过max_frame_size的报告被丢弃
+    let req = LogFrame::Report(line(3, &"d".repeat(64)));
+    s.serve(bincode::serialize(&req).unwrap());
+    assert_eq!(*entity.0.lock().unwrap(), vec!["a", "c"]);
+}
+
+#[test]
+fn build_12_limits() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/12_limits.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(StoreRequest);
+    show_type!(LogRequest);
+    show_type!(StoreProxy);
+}
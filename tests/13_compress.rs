@@ -0,0 +1,214 @@
+// -- 13_compress.rs --
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface(compress = "zstd", compress_threshold = 64)]
+pub trait Archive {
+    #[servant(compress = "lz4")]
+    fn dump(&self) -> Vec<String>;
+    fn store(&mut self, lines: Vec<String>) -> usize;
+    fn load(&self) -> Vec<String>;
+}
+
+#[servant::invoke_interface(compress = "zstd", compress_threshold = 64, max_frame_size = 1024)]
+pub trait Blob {
+    fn put(&mut self, data: Vec<u8>) -> usize;
+}
+
+// --
+
+#[derive(Default)]
+struct ArchiveEntity(Vec<String>);
+impl Archive for ArchiveEntity {
+    fn store(&mut self, _ctx: Option<servant::Context>, lines: Vec<String>) -> usize {
+        self.0.extend(lines);
+        self.0.len()
+    }
+    fn load(&self, _ctx: Option<servant::Context>) -> Vec<String> {
+        self.0.clone()
+    }
+    fn dump(&self, _ctx: Option<servant::Context>) -> Vec<String> {
+        self.0.clone()
+    }
+}
+
+struct BlobEntity;
+impl Blob for BlobEntity {
+    fn put(&mut self, _ctx: Option<servant::Context>, data: Vec<u8>) -> usize {
+        data.len()
+    }
+}
+
+// --
+
+fn lines(count: usize) -> Vec<String> {
+    (0..count).map(|_| "the same line".to_string()).collect()
+}
+
+// 与servant协商之后的proxy相同，按照方法的compress压缩请求
+fn call(s: &ArchiveServant<ArchiveEntity>, req: ArchiveRequest) -> Vec<u8> {
+    use servant::Servant;
+
+    let mut ctx = servant::Context::default();
+    ctx.set_accept_compress(true);
+    let compression = req.compression();
+    let req = bincode::serialize(&ArchiveFrame::Call(req)).unwrap();
+    s.serve(Some(ctx), ArchiveRequest::compress(compression, req))
+}
+
+fn decode<T: serde::de::DeserializeOwned>(reps: Vec<u8>) -> servant::ServantResult<T> {
+    let (_, v) = ArchiveRequest::decompress(reps, None).unwrap();
+    bincode::deserialize(&v).unwrap()
+}
+
+#[test]
+fn test_archive_compress() {
+    show_type!(ArchiveServant<ArchiveEntity>);
+    let s = ArchiveServant::new("archive1", ArchiveEntity::default());
+
+    let reps = call(&s, ArchiveRequest::Store { lines: lines(100) });
+    // 响应很小，没有压缩，是原来的bincode数据
+    assert_eq!(reps[0], 0);
+    assert_eq!(decode::<usize>(reps).unwrap(), 100);
+
+    let plain = bincode::serialize(&servant::ServantResult::Ok(lines(100))).unwrap();
+    let reps = call(&s, ArchiveRequest::Load {});
+    assert_eq!(reps[0], 0xfe);
+    assert!(reps.len() < plain.len());
+    assert_eq!(decode::<Vec<String>>(reps).unwrap(), lines(100));
+
+    let reps = call(&s, ArchiveRequest::Dump {});
+    assert_eq!(reps[0], 0xfd);
+    assert!(reps.len() < plain.len());
+    assert_eq!(decode::<Vec<String>>(reps).unwrap(), lines(100));
+}
+
+#[test]
+fn test_archive_uncompressed_peer() {
+    use servant::Servant;
+
+    let s = ArchiveServant::new("archive1", ArchiveEntity::default());
+    // 没有压缩的请求，Context中也没有accept_compress，servant不压缩响应
+    let req = ArchiveFrame::Call(ArchiveRequest::Store { lines: lines(100) });
+    s.serve(None, bincode::serialize(&req).unwrap());
+    let req = ArchiveFrame::Call(ArchiveRequest::Load {});
+    let reps = s.serve(None, bincode::serialize(&req).unwrap());
+    assert_eq!(
+        bincode::deserialize::<servant::ServantResult<Vec<String>>>(&reps)
+            .unwrap()
+            .unwrap(),
+        lines(100)
+    );
+
+    // Context中有accept_compress时，压缩响应
+    let mut ctx = servant::Context::default();
+    ctx.set_accept_compress(true);
+    let reps = s.serve(Some(ctx), bincode::serialize(&req).unwrap());
+    assert_eq!(reps[0], 0xfe);
+    assert_eq!(decode::<Vec<String>>(reps).unwrap(), lines(100));
+}
+
+#[test]
+fn test_archive_proxy_negotiate() {
+    use servant::Servant;
+    use std::sync::{Arc, Mutex};
+
+    // 记录proxy发送的请求的第一个字节，以及Context中的accept_compress
+    let s = ArchiveServant::new("archive1", ArchiveEntity::default());
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let r = sent.clone();
+    let t = servant::Terminal::loopback(move |call| match call {
+        servant::Loopback::Invoke { ctx, req, .. } => {
+            let accept = matches!(&ctx, Some(c) if c.accept_compress());
+            r.lock().unwrap().push((req[0], accept));
+            Ok(s.serve(ctx, req))
+        }
+        _ => unreachable!(),
+    });
+    let mut proxy = ArchiveProxy::new(servant::Context::default(), "archive1", &t);
+    futures::executor::block_on(async {
+        // 收到压缩的响应之前，proxy不压缩请求
+        assert_eq!(proxy.store(lines(100)).await.unwrap(), 100);
+        assert_eq!(proxy.load().await.unwrap(), lines(100));
+        assert_eq!(proxy.store(lines(100)).await.unwrap(), 200);
+        let mut batch = proxy.batch();
+        batch.store(lines(100)).dump();
+        let reps = batch.call().await.unwrap();
+        assert!(matches!(&reps[0], ArchiveResponse::Store(Ok(300))));
+        assert!(matches!(&reps[1], ArchiveResponse::Dump(Ok(v)) if v.len() == 300));
+    });
+    // batch使用接口的zstd，而不是第一个方法的lz4
+    assert_eq!(
+        *sent.lock().unwrap(),
+        vec![(0, true), (0, true), (0xfe, true), (0xfe, true)]
+    );
+}
+
+#[test]
+fn test_archive_batch_compress() {
+    use servant::Servant;
+
+    let s = ArchiveServant::new("archive1", ArchiveEntity::default());
+    let req = ArchiveFrame::Batch(vec![
+        ArchiveRequest::Store { lines: lines(100) },
+        ArchiveRequest::Load {},
+    ]);
+    let req = ArchiveRequest::compress(
+        ArchiveRequest::Load {}.compression(),
+        bincode::serialize(&req).unwrap(),
+    );
+    assert_eq!(req[0], 0xfe);
+    let reps: Vec<Vec<u8>> = bincode::deserialize(&s.serve(None, req)).unwrap();
+    assert_eq!(decode::<usize>(reps[0].clone()).unwrap(), 100);
+    assert_eq!(reps[1][0], 0xfe);
+    assert_eq!(decode::<Vec<String>>(reps[1].clone()).unwrap(), lines(100));
+}
+
+#[test]
+fn test_blob_max_frame_size() {
+    use servant::Servant;
+
+    let s = BlobServant::new("blob1", BlobEntity);
+    let put = |size: usize| {
+        let req = BlobRequest::Put {
+            data: vec![0; size],
+        };
+        let compression = req.compression();
+        let req = bincode::serialize(&BlobFrame::Call(req)).unwrap();
+        let req = BlobRequest::compress(compression, req);
+        let reps = s.serve(None, req.clone());
+        (req.len(), reps)
+    };
+
+    let (_, reps) = put(512);
+    let (_, v) = BlobRequest::decompress(reps, None).unwrap();
+    let reps: servant::ServantResult<usize> = bincode::deserialize(&v).unwrap();
+    assert_eq!(reps.unwrap(), 512);
+
+    // 压缩之后的请求很小，但是解压之后超过了max_frame_size
+    let (size, reps) = put(100_000);
+    assert!(size < 1024);
+    let reps: servant::ServantResult<usize> = bincode::deserialize(&reps).unwrap();
+    assert!(reps
+        .unwrap_err()
+        .to_string()
+        .contains("exceeds max_frame_size 1024"));
+}
+
+#[test]
+fn build_13_compress() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/13_compress.rs");
+}
+
+// --
+
+fn main() {
+    show_type!(ArchiveRequest);
+    show_type!(ArchiveProxy);
+    show_type!(BlobRequest);
+}
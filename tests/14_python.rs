@@ -0,0 +1,152 @@
+// -- 14_python.rs --
+
+// 需要打开python feature：cargo test --features python --test 14_python。
+// trybuild不运行build.rs，没有OUT_DIR，所以这里没有build_14_python。
+#![cfg(feature = "python")]
+
+#[macro_use]
+mod utilities;
+
+// --
+
+// these code are been tested.
+#[servant::invoke_interface]
+pub trait Hello {
+    fn hello(&self, n: i32, names: Vec<String>) -> Option<String>;
+    fn bye(&mut self);
+    fn put(
+        &mut self,
+        key: (u8, char),
+        value: Option<std::collections::BTreeMap<String, f64>>,
+        flag: bool,
+        big: u128,
+    ) -> Vec<(u64, bool)>;
+    fn lambda(&self, from: u8, out: u16) -> u8;
+}
+
+#[servant::watch_interface]
+pub trait Clock {
+    fn now(&self, zone: String) -> String;
+}
+
+// --
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// 运行导出到OUT_DIR中的Python客户端代码，返回输出的每一行
+fn python(script: &str) -> Vec<String> {
+    let output = std::process::Command::new("python3")
+        .arg("-c")
+        .arg(script)
+        .current_dir(concat!(env!("OUT_DIR"), "/servant"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+// Python生成请求，与Rust的bincode编码比较；Rust编码响应，由Python解码
+#[test]
+#[cfg_attr(not(servant_python3), ignore = "python3 is not found")]
+fn test_hello_python() {
+    show_type!(HelloProxy);
+    let ok: servant::ServantResult<Option<String>> = Ok(Some("hi".to_string()));
+    let none: servant::ServantResult<Option<String>> = Ok(None);
+    let list: servant::ServantResult<Vec<(u64, bool)>> = Ok(vec![(7, true), (u64::MAX, false)]);
+    let err: servant::ServantResult<Vec<(u64, bool)>> = Err("oops".into());
+    let script = format!(
+        r#"
+from Hello import FRAME, HelloRequest, HelloResponse
+from servant import ServantError
+print((FRAME + HelloRequest.hello(88, ["a", "bc"])).hex())
+print((FRAME + HelloRequest.bye()).hex())
+print((FRAME + HelloRequest.put((1, "中"), {{"pi": 3.5}}, True, 2 ** 100)).hex())
+print((FRAME + HelloRequest.put((2, "x"), None, False, 0)).hex())
+print((FRAME + HelloRequest.lambda_(7, 300)).hex())
+print(repr(HelloResponse.hello(bytes.fromhex("{}"))))
+print(repr(HelloResponse.hello(bytes.fromhex("{}"))))
+print(repr(HelloResponse.put(bytes.fromhex("{}"))))
+try:
+    HelloResponse.put(bytes.fromhex("{}"))
+except ServantError:
+    print("ServantError")
+"#,
+        hex(&bincode::serialize(&ok).unwrap()),
+        hex(&bincode::serialize(&none).unwrap()),
+        hex(&bincode::serialize(&list).unwrap()),
+        hex(&bincode::serialize(&err).unwrap()),
+    );
+
+    let mut value = std::collections::BTreeMap::new();
+    value.insert("pi".to_string(), 3.5);
+    let frames = [
+        HelloFrame::Call(HelloRequest::Hello {
+            n: 88,
+            names: vec!["a".to_string(), "bc".to_string()],
+        }),
+        HelloFrame::Call(HelloRequest::Bye {}),
+        HelloFrame::Call(HelloRequest::Put {
+            key: (1, '中'),
+            value: Some(value),
+            flag: true,
+            big: 1 << 100,
+        }),
+        HelloFrame::Call(HelloRequest::Put {
+            key: (2, 'x'),
+            value: None,
+            flag: false,
+            big: 0,
+        }),
+        HelloFrame::Call(HelloRequest::Lambda { from: 7, out: 300 }),
+    ];
+    let mut expected: Vec<_> = frames
+        .iter()
+        .map(|frame| hex(&bincode::serialize(frame).unwrap()))
+        .collect();
+    expected.extend([
+        "'hi'".to_string(),
+        "None".to_string(),
+        format!("[(7, True), ({}, False)]", u64::MAX),
+        "ServantError".to_string(),
+    ]);
+    assert_eq!(python(&script), expected);
+}
+
+// watch接口的请求没有frame
+#[test]
+#[cfg_attr(not(servant_python3), ignore = "python3 is not found")]
+fn test_clock_python() {
+    let ok: servant::ServantResult<String> = Ok("12:00".to_string());
+    let script = format!(
+        r#"
+from Clock import FRAME, ClockRequest, ClockResponse
+print((FRAME + ClockRequest.now("UTC")).hex())
+print(ClockResponse.now(bytes.fromhex("{}")))
+"#,
+        hex(&bincode::serialize(&ok).unwrap()),
+    );
+    let req = ClockRequest::Now {
+        zone: "UTC".to_string(),
+    };
+    assert_eq!(
+        python(&script),
+        vec![hex(&bincode::serialize(&req).unwrap()), "12:00".to_string()]
+    );
+}
+
+// --
+
+fn main() {
+    show_type!(HelloRequest);
+    show_type!(ClockRequest);
+}